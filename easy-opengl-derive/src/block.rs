@@ -0,0 +1,134 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Error, Fields, LitInt, Result};
+
+/// The GLSL block layouts that can be derived
+#[derive(Clone, Copy)]
+pub enum Layout {
+    Std140,
+    Std430,
+}
+
+impl Layout {
+    fn name(&self) -> &'static str {
+        match self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+        }
+    }
+}
+
+/// Parse the `#[std140(size = N)]` attribute of the struct
+fn parse_expected_size(input: &DeriveInput, layout: Layout) -> Result<Option<usize>> {
+    let mut size = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident(layout.name()))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("size") {
+                let lit: LitInt = meta.value()?.parse()?;
+                size = Some(lit.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `size = N`"))
+            }
+        })?;
+    }
+    Ok(size)
+}
+
+pub fn expand(input: DeriveInput, layout: Layout) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "the block layouts can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "the block layouts can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = layout.name();
+    let trait_name = format_ident!("{}", name.replace("std", "Std"));
+    let element_trait = format_ident!("{}ArrayElement", trait_name);
+    let align = format_ident!("{}_ALIGN", name.to_uppercase());
+    let size = format_ident!("{}_SIZE", name.to_uppercase());
+    let write = format_ident!("write_{}", name);
+    let layout_path = quote!(::easy_opengl::layout);
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let idents: Vec<_> = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap())
+        .collect();
+
+    // On std140 the alignment of a struct is rounded up to the alignment of a vec4
+    let min_align = match layout {
+        Layout::Std140 => 16usize,
+        Layout::Std430 => 1usize,
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let size_check = parse_expected_size(&input, layout)?.map(|expected| {
+        let message = format!(
+            "the {} size of `{}` doesn't match the declared size {}",
+            name, ident, expected
+        );
+        quote! {
+            const _: () = ::core::assert!(
+                <#ident as #layout_path::#trait_name>::#size == #expected,
+                #message
+            );
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #layout_path::#trait_name for #ident #ty_generics #where_clause {
+            const #align: usize = {
+                let align = #min_align;
+                #(let align = #layout_path::max(align, <#types as #layout_path::#trait_name>::#align);)*
+                align
+            };
+            const #size: usize = {
+                let offset = 0usize;
+                #(
+                    let offset = #layout_path::align_up(
+                        offset,
+                        <#types as #layout_path::#trait_name>::#align,
+                    ) + <#types as #layout_path::#trait_name>::#size;
+                )*
+                #layout_path::align_up(offset, Self::#align)
+            };
+
+            fn #write(&self, out: &mut [u8]) {
+                let offset = 0usize;
+                #(
+                    let offset = #layout_path::align_up(
+                        offset,
+                        <#types as #layout_path::#trait_name>::#align,
+                    );
+                    let end = offset + <#types as #layout_path::#trait_name>::#size;
+                    #layout_path::#trait_name::#write(&self.#idents, &mut out[offset..end]);
+                    let offset = end;
+                )*
+                let _ = offset;
+            }
+        }
+
+        impl #impl_generics #layout_path::#element_trait for #ident #ty_generics #where_clause {}
+
+        #size_check
+    })
+}
@@ -0,0 +1,57 @@
+//! # easy-opengl-derive
+//!
+//! Derive macros used by `easy-opengl`. The macros generate code that refers to
+//! `::easy_opengl`, so use them through the re-exports of that crate.
+
+extern crate proc_macro;
+
+mod block;
+mod vertex;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Generate a `easy_opengl::buffers::Vertex` implementation that describe the layout of
+/// the struct
+///
+/// # Field attributes
+/// * `#[vertex(normalize)]` - Normalize the integer values of the field
+/// * `#[vertex(format = Half2)]` - Use a explicit `VertexAttribType`, needed by the half floats
+///   and the packed formats. It fails to compile if the size of the field doesn't match it
+/// * `#[vertex(location = 3)]` - Use a explicit attribute location
+/// * `#[vertex(divisor = 1)]` - Advance the attribute every N instances instead of every vertex
+/// * `#[vertex(name = "aPos")]` - Attribute name, by default the field name
+/// * `#[vertex(skip)]` - The field isn't a vertex attribute
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex::expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Generate a `easy_opengl::layout::Std140` implementation that write the struct with the
+/// `std140` layout of the uniform blocks
+///
+/// # Struct attributes
+/// * `#[std140(size = 80)]` - Fail to compile if the size of the layout isn't the given one
+#[proc_macro_derive(Std140, attributes(std140))]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    block::expand(input, block::Layout::Std140)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Generate a `easy_opengl::layout::Std430` implementation that write the struct with the
+/// `std430` layout of the shader storage blocks
+///
+/// # Struct attributes
+/// * `#[std430(size = 80)]` - Fail to compile if the size of the layout isn't the given one
+#[proc_macro_derive(Std430, attributes(std430))]
+pub fn derive_std430(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    block::expand(input, block::Layout::Std430)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -0,0 +1,142 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr, Result};
+
+struct FieldConfig {
+    normalize: bool,
+    location: Option<u32>,
+    divisor: Option<u32>,
+    name: Option<String>,
+    format: Option<Ident>,
+    skip: bool,
+}
+
+fn parse_field_config(field: &syn::Field) -> Result<FieldConfig> {
+    let mut config = FieldConfig {
+        normalize: false,
+        location: None,
+        divisor: None,
+        name: None,
+        format: None,
+        skip: false,
+    };
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("normalize") {
+                config.normalize = true;
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                config.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("location") {
+                let lit: LitInt = meta.value()?.parse()?;
+                config.location = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("divisor") {
+                let lit: LitInt = meta.value()?.parse()?;
+                config.divisor = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                let lit: LitStr = meta.value()?.parse()?;
+                config.name = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("format") {
+                config.format = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `normalize`, `skip`, `location = N`, `divisor = N`, `name = \"..\"` or `format = Type`"))
+            }
+        })?;
+    }
+
+    Ok(config)
+}
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut attribs = Vec::new();
+    let mut size_checks = Vec::new();
+    for field in fields {
+        let config = parse_field_config(field)?;
+        if config.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let normalize = config.normalize;
+        let name = config.name.unwrap_or_else(|| ident.to_string());
+        let location = config
+            .location
+            .map(|location| quote!(.with_location(#location)));
+        let divisor = config
+            .divisor
+            .map(|divisor| quote!(.with_divisor(#divisor)));
+
+        let vtype = match &config.format {
+            Some(format) => quote!(::easy_opengl::buffers::VertexAttribType::#format),
+            None => quote!(<#ty as ::easy_opengl::buffers::VertexAttribFormat>::VERTEX_ATTRIB_TYPE),
+        };
+
+        // A explicit format isn't checked against the type, gl would read outside the field
+        if let Some(format) = &config.format {
+            let message = format!(
+                "the size of the field `{}` doesn't match the vertex format {}",
+                ident, format
+            );
+            size_checks.push(quote! {
+                const {
+                    ::core::assert!(
+                        ::core::mem::size_of::<#ty>()
+                            == ::easy_opengl::buffers::vertex_attrib_type_size(&#vtype) as usize,
+                        #message
+                    )
+                };
+            });
+        }
+
+        attribs.push(quote! {
+            ::easy_opengl::buffers::VertexAttrib::new(
+                #vtype,
+                #normalize,
+                #name.to_string(),
+            )
+            .with_offset(::core::mem::offset_of!(Self, #ident) as u32)
+            #location
+            #divisor
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::easy_opengl::buffers::Vertex for #ident #ty_generics #where_clause {
+            fn layout() -> ::easy_opengl::buffers::VertexLayout {
+                #(#size_checks)*
+                ::easy_opengl::buffers::VertexLayout::new(
+                    ::core::mem::size_of::<Self>() as u32,
+                    ::std::vec![#(#attribs),*],
+                )
+            }
+        }
+    })
+}
@@ -1,7 +1,10 @@
 extern crate gl;
 extern crate sdl2;
 
+use bytemuck::{Pod, Zeroable};
 use easy_opengl::buffers::*;
+use easy_opengl::draw::*;
+use easy_opengl::mesh::*;
 use easy_opengl::shader::*;
 use easy_opengl::textures::*;
 
@@ -37,8 +40,12 @@ const FRAGMENT_SHADER_SOURCE: &str = r#"
     }
 "#;
 
-pub struct Vertex {
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+pub struct QuadVertex {
+    #[vertex(name = "aPos")]
     pub pos: [f32; 3],
+    #[vertex(name = "aTexCoord")]
     pub uv: [f32; 2],
 }
 
@@ -83,42 +90,35 @@ impl Window {
 pub fn main() {
     let mut window = Window::new(SCR_WIDTH, SCR_HEIGHT, "Test".to_string());
     let mut shader = Shader::new();
-    shader.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None);
+    if let Err(err) = shader.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None) {
+        panic!("{}", err);
+    }
 
     let vertices = vec![
-        Vertex {
+        QuadVertex {
             pos: [0.5, 0.5, 0.0],
             uv: [1.0, 1.0],
         }, // top right
-        Vertex {
+        QuadVertex {
             pos: [0.5, -0.5, 0.0],
             uv: [1.0, 0.0],
         }, // bottom right
-        Vertex {
+        QuadVertex {
             pos: [-0.5, -0.5, 0.0],
             uv: [0.0, 0.0],
         }, // bottom left
-        Vertex {
+        QuadVertex {
             pos: [-0.5, 0.5, 0.0],
             uv: [0.0, 1.0],
         }, // top left
     ];
-    let indices = vec![
+    let indices: Vec<u16> = vec![
         0, 1, 3, // first Triangle
         1, 2, 3, // second Triangle
     ];
 
-    let vao = VertexArray::new();
-    let _vbo = VertexBuffer::new(calc_bytes_size(&vertices) as isize, Some(&vertices));
-
-    vao.bind();
-
-    submit_vertex_attribs(&mut vec![
-        VertexAttrib::new(VertexAttribType::Float3, false, "aPos".to_string()),
-        VertexAttrib::new(VertexAttribType::Float2, false, "aTexCoord".to_string()),
-    ]);
-
-    let _ibo = IndexBuffer::new(calc_bytes_size(&indices) as isize, Some(&indices));
+    // The mesh keeps the buffers alive
+    let quad = Mesh::indexed(Topology::Triangles, &vertices, &indices);
 
     let mut texture = Texture2D::new();
     texture.load_from_file("./a.png", TextureConfig::new());
@@ -132,13 +132,7 @@ pub fn main() {
                 texture.bind();
                 shader.bind();
                 shader.set_uniform("color", UniformType::Fv4(0.1, 0.4, 0.2, 1.0));
-                vao.bind();
-                gl::DrawElements(
-                    gl::TRIANGLES,
-                    indices.len() as i32,
-                    gl::UNSIGNED_INT,
-                    std::ptr::null(),
-                );
+                quad.draw();
             }
             match event {
                 Event::Quit { .. } => {
@@ -1,19 +1,77 @@
+use std::cell::{Cell, RefCell};
 use std::ffi::c_void;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::rc::Rc;
 
-#[derive(Clone, Copy)]
+use bytemuck::{Pod, Zeroable};
+
+use crate::layout::{Std140, Std430};
+use crate::shader::Shader;
+use crate::sync::Fence;
+
+pub use easy_opengl_derive::Vertex;
+
+/// The format of a vertex attribute on the buffer. The integer formats are sent as integers
+/// to the shader (`int`, `ivec`, `uint`, `uvec`), unless the attribute is normalized, then the
+/// shader receives floats on the range `[0, 1]` for the unsigned formats or `[-1, 1]` for the
+/// signed ones. The packed formats are always sent as floats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VertexAttribType {
+    /// 32 bits float
     Float,
     Float2,
     Float3,
     Float4,
     Mat3,
     Mat4,
+    /// 16 bits float, the rust type is the bits in a `u16`
+    Half,
+    Half2,
+    Half3,
+    Half4,
+    /// 64 bits float, the shader input has to be a `double` or `dvec`
+    Double,
+    Double2,
+    Double3,
+    Double4,
+    /// 32 bits signed integer
     Int,
     Int2,
     Int3,
     Int4,
+    /// 32 bits unsigned integer
     Uint,
+    Uint2,
+    Uint3,
+    Uint4,
+    /// 16 bits signed integer
+    Short,
+    Short2,
+    Short3,
+    Short4,
+    /// 16 bits unsigned integer
+    Ushort,
+    Ushort2,
+    Ushort3,
+    Ushort4,
+    /// 8 bits signed integer
     Byte,
+    Byte2,
+    Byte3,
+    Byte4,
+    /// 8 bits unsigned integer
+    Ubyte,
+    Ubyte2,
+    Ubyte3,
+    Ubyte4,
+    /// A signed `vec4` packed in 32 bits, 10 bits for x, y and z and 2 bits for w
+    Int2101010Rev,
+    /// A unsigned `vec4` packed in 32 bits, 10 bits for x, y and z and 2 bits for w
+    Uint2101010Rev,
+    /// A `vec3` of small unsigned floats packed in 32 bits
+    Uint10F11F11FRev,
 }
 
 pub fn vertex_attrib_type_gl(vtype: &VertexAttribType) -> u32 {
@@ -24,16 +82,46 @@ pub fn vertex_attrib_type_gl(vtype: &VertexAttribType) -> u32 {
         VertexAttribType::Float4 => gl::FLOAT,
         VertexAttribType::Mat3 => gl::FLOAT,
         VertexAttribType::Mat4 => gl::FLOAT,
+        VertexAttribType::Half => gl::HALF_FLOAT,
+        VertexAttribType::Half2 => gl::HALF_FLOAT,
+        VertexAttribType::Half3 => gl::HALF_FLOAT,
+        VertexAttribType::Half4 => gl::HALF_FLOAT,
+        VertexAttribType::Double => gl::DOUBLE,
+        VertexAttribType::Double2 => gl::DOUBLE,
+        VertexAttribType::Double3 => gl::DOUBLE,
+        VertexAttribType::Double4 => gl::DOUBLE,
         VertexAttribType::Int => gl::INT,
         VertexAttribType::Int2 => gl::INT,
         VertexAttribType::Int3 => gl::INT,
         VertexAttribType::Int4 => gl::INT,
         VertexAttribType::Uint => gl::UNSIGNED_INT,
+        VertexAttribType::Uint2 => gl::UNSIGNED_INT,
+        VertexAttribType::Uint3 => gl::UNSIGNED_INT,
+        VertexAttribType::Uint4 => gl::UNSIGNED_INT,
+        VertexAttribType::Short => gl::SHORT,
+        VertexAttribType::Short2 => gl::SHORT,
+        VertexAttribType::Short3 => gl::SHORT,
+        VertexAttribType::Short4 => gl::SHORT,
+        VertexAttribType::Ushort => gl::UNSIGNED_SHORT,
+        VertexAttribType::Ushort2 => gl::UNSIGNED_SHORT,
+        VertexAttribType::Ushort3 => gl::UNSIGNED_SHORT,
+        VertexAttribType::Ushort4 => gl::UNSIGNED_SHORT,
         VertexAttribType::Byte => gl::BYTE,
+        VertexAttribType::Byte2 => gl::BYTE,
+        VertexAttribType::Byte3 => gl::BYTE,
+        VertexAttribType::Byte4 => gl::BYTE,
+        VertexAttribType::Ubyte => gl::UNSIGNED_BYTE,
+        VertexAttribType::Ubyte2 => gl::UNSIGNED_BYTE,
+        VertexAttribType::Ubyte3 => gl::UNSIGNED_BYTE,
+        VertexAttribType::Ubyte4 => gl::UNSIGNED_BYTE,
+        VertexAttribType::Int2101010Rev => gl::INT_2_10_10_10_REV,
+        VertexAttribType::Uint2101010Rev => gl::UNSIGNED_INT_2_10_10_10_REV,
+        VertexAttribType::Uint10F11F11FRev => gl::UNSIGNED_INT_10F_11F_11F_REV,
     }
 }
 
-pub fn vertex_attrib_type_size(vtype: &VertexAttribType) -> u32 {
+/// The size in bytes of the attribute
+pub const fn vertex_attrib_type_size(vtype: &VertexAttribType) -> u32 {
     match vtype {
         VertexAttribType::Float => 4,
         VertexAttribType::Float2 => 4 * 2,
@@ -41,15 +129,46 @@ pub fn vertex_attrib_type_size(vtype: &VertexAttribType) -> u32 {
         VertexAttribType::Float4 => 4 * 4,
         VertexAttribType::Mat3 => 4 * 3 * 3,
         VertexAttribType::Mat4 => 4 * 4 * 4,
+        VertexAttribType::Half => 2,
+        VertexAttribType::Half2 => 2 * 2,
+        VertexAttribType::Half3 => 2 * 3,
+        VertexAttribType::Half4 => 2 * 4,
+        VertexAttribType::Double => 8,
+        VertexAttribType::Double2 => 8 * 2,
+        VertexAttribType::Double3 => 8 * 3,
+        VertexAttribType::Double4 => 8 * 4,
         VertexAttribType::Int => 4,
         VertexAttribType::Int2 => 4 * 2,
         VertexAttribType::Int3 => 4 * 3,
         VertexAttribType::Int4 => 4 * 4,
         VertexAttribType::Uint => 4,
+        VertexAttribType::Uint2 => 4 * 2,
+        VertexAttribType::Uint3 => 4 * 3,
+        VertexAttribType::Uint4 => 4 * 4,
+        VertexAttribType::Short => 2,
+        VertexAttribType::Short2 => 2 * 2,
+        VertexAttribType::Short3 => 2 * 3,
+        VertexAttribType::Short4 => 2 * 4,
+        VertexAttribType::Ushort => 2,
+        VertexAttribType::Ushort2 => 2 * 2,
+        VertexAttribType::Ushort3 => 2 * 3,
+        VertexAttribType::Ushort4 => 2 * 4,
         VertexAttribType::Byte => 1,
+        VertexAttribType::Byte2 => 2,
+        VertexAttribType::Byte3 => 3,
+        VertexAttribType::Byte4 => 4,
+        VertexAttribType::Ubyte => 1,
+        VertexAttribType::Ubyte2 => 2,
+        VertexAttribType::Ubyte3 => 3,
+        VertexAttribType::Ubyte4 => 4,
+        VertexAttribType::Int2101010Rev => 4,
+        VertexAttribType::Uint2101010Rev => 4,
+        VertexAttribType::Uint10F11F11FRev => 4,
     }
 }
 
+/// The number of components of the attribute, a packed format has its components inside a
+/// single 32 bits value
 pub fn vertex_attrib_type_count(vtype: &VertexAttribType) -> u32 {
     match vtype {
         VertexAttribType::Float => 1,
@@ -58,12 +177,51 @@ pub fn vertex_attrib_type_count(vtype: &VertexAttribType) -> u32 {
         VertexAttribType::Float4 => 4,
         VertexAttribType::Mat3 => 3 * 3,
         VertexAttribType::Mat4 => 4 * 4,
+        VertexAttribType::Half => 1,
+        VertexAttribType::Half2 => 2,
+        VertexAttribType::Half3 => 3,
+        VertexAttribType::Half4 => 4,
+        VertexAttribType::Double => 1,
+        VertexAttribType::Double2 => 2,
+        VertexAttribType::Double3 => 3,
+        VertexAttribType::Double4 => 4,
         VertexAttribType::Int => 1,
         VertexAttribType::Int2 => 2,
         VertexAttribType::Int3 => 3,
         VertexAttribType::Int4 => 4,
         VertexAttribType::Uint => 1,
+        VertexAttribType::Uint2 => 2,
+        VertexAttribType::Uint3 => 3,
+        VertexAttribType::Uint4 => 4,
+        VertexAttribType::Short => 1,
+        VertexAttribType::Short2 => 2,
+        VertexAttribType::Short3 => 3,
+        VertexAttribType::Short4 => 4,
+        VertexAttribType::Ushort => 1,
+        VertexAttribType::Ushort2 => 2,
+        VertexAttribType::Ushort3 => 3,
+        VertexAttribType::Ushort4 => 4,
         VertexAttribType::Byte => 1,
+        VertexAttribType::Byte2 => 2,
+        VertexAttribType::Byte3 => 3,
+        VertexAttribType::Byte4 => 4,
+        VertexAttribType::Ubyte => 1,
+        VertexAttribType::Ubyte2 => 2,
+        VertexAttribType::Ubyte3 => 3,
+        VertexAttribType::Ubyte4 => 4,
+        VertexAttribType::Int2101010Rev => 4,
+        VertexAttribType::Uint2101010Rev => 4,
+        VertexAttribType::Uint10F11F11FRev => 3,
+    }
+}
+
+/// The number of consecutive locations used by the attribute, the matrices use one for each
+/// column
+pub fn vertex_attrib_type_locations(vtype: &VertexAttribType) -> u32 {
+    match vtype {
+        VertexAttribType::Mat3 => 3,
+        VertexAttribType::Mat4 => 4,
+        _ => 1,
     }
 }
 
@@ -75,7 +233,7 @@ pub fn vertex_attrib_type_count(vtype: &VertexAttribType) -> u32 {
 /// let attrib = VertexAttrib::new(
 ///       VertexAttribType::Float3, // We want to send a vector3
 ///       false, // normalize
-///       "pos".to_string(), // the name is only to know the which attribute is to the end user
+///       "pos".to_string(), // the name of the attribute on the shader, used by resolve_locations
 /// )
 /// ```
 pub struct VertexAttrib {
@@ -84,6 +242,11 @@ pub struct VertexAttrib {
     pub vtype: VertexAttribType,
     pub normalize: bool,
     pub name: String,
+    /// The explicit location of the attribute, if is None the location is the next of the
+    /// previous attribute
+    pub location: Option<u32>,
+    /// The step rate of the attribute, 0 advance every vertex and N advance every N instances
+    pub divisor: u32,
 }
 
 impl VertexAttrib {
@@ -94,15 +257,237 @@ impl VertexAttrib {
             vtype,
             normalize,
             name,
+            location: None,
+            divisor: 0,
+        }
+    }
+
+    /// Set the offset in bytes of the attribute inside the vertex
+    pub fn with_offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set a explicit location to the attribute
+    pub fn with_location(mut self, location: u32) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Make the attribute advance once every `divisor` instances instead of every vertex
+    ///
+    /// # Example
+    ///
+    /// ``` Rust
+    /// // A per instance transform, it use the locations 2, 3, 4 and 5
+    /// let model = VertexAttrib::new(VertexAttribType::Mat4, false, "aModel".to_string())
+    ///     .with_location(2)
+    ///     .with_divisor(1);
+    /// ```
+    pub fn with_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+}
+
+/// Map a rust type to the vertex attribute type used to send it to the gpu. It's used by
+/// `#[derive(Vertex)]` to know the type of each field, the half floats and the packed formats
+/// don't have a rust type so the field needs `#[vertex(format = Half2)]`
+pub trait VertexAttribFormat {
+    const VERTEX_ATTRIB_TYPE: VertexAttribType;
+}
+
+macro_rules! impl_vertex_attrib_format {
+    ($($ty:ty => $vtype:ident),* $(,)?) => {
+        $(
+            impl VertexAttribFormat for $ty {
+                const VERTEX_ATTRIB_TYPE: VertexAttribType = VertexAttribType::$vtype;
+            }
+        )*
+    };
+}
+
+impl_vertex_attrib_format! {
+    f32 => Float,
+    [f32; 1] => Float,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    [[f32; 3]; 3] => Mat3,
+    [[f32; 4]; 4] => Mat4,
+    f64 => Double,
+    [f64; 1] => Double,
+    [f64; 2] => Double2,
+    [f64; 3] => Double3,
+    [f64; 4] => Double4,
+    i32 => Int,
+    [i32; 1] => Int,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    u32 => Uint,
+    [u32; 1] => Uint,
+    [u32; 2] => Uint2,
+    [u32; 3] => Uint3,
+    [u32; 4] => Uint4,
+    i16 => Short,
+    [i16; 1] => Short,
+    [i16; 2] => Short2,
+    [i16; 3] => Short3,
+    [i16; 4] => Short4,
+    u16 => Ushort,
+    [u16; 1] => Ushort,
+    [u16; 2] => Ushort2,
+    [u16; 3] => Ushort3,
+    [u16; 4] => Ushort4,
+    i8 => Byte,
+    [i8; 1] => Byte,
+    [i8; 2] => Byte2,
+    [i8; 3] => Byte3,
+    [i8; 4] => Byte4,
+    u8 => Ubyte,
+    [u8; 1] => Ubyte,
+    [u8; 2] => Ubyte2,
+    [u8; 3] => Ubyte3,
+    [u8; 4] => Ubyte4,
+}
+
+/// The layout of the vertices of a buffer, the stride is the size in bytes of one vertex
+///
+/// # Example
+///
+/// ``` Rust
+/// #[repr(C)]
+/// #[derive(Vertex)]
+/// pub struct MyVertex {
+///     pub pos: [f32; 3],
+///     #[vertex(normalize)]
+///     pub color: [u8; 4],
+///     #[vertex(format = Int2101010Rev, normalize)]
+///     pub normal: u32,
+///     #[vertex(skip)]
+///     pub id: u64,
+/// }
+///
+/// let vao = VertexArray::new();
+/// let _vbo = VertexBuffer::from_slice(&vertices);
+/// vao.bind();
+/// submit_vertex_layout(&MyVertex::layout());
+/// ```
+pub struct VertexLayout {
+    pub stride: u32,
+    pub attribs: Vec<VertexAttrib>,
+}
+
+impl VertexLayout {
+    /// Return a layout with the offsets already set on the attributes
+    pub fn new(stride: u32, attribs: Vec<VertexAttrib>) -> Self {
+        Self { stride, attribs }
+    }
+
+    /// Return a layout where the attributes are tightly packed one after another
+    pub fn packed(mut attribs: Vec<VertexAttrib>) -> Self {
+        let mut offset = 0;
+        for attrib in attribs.iter_mut() {
+            attrib.offset = offset;
+            offset += attrib.size;
+        }
+
+        Self {
+            stride: offset,
+            attribs,
+        }
+    }
+
+    /// Set the location of each attribute to the location of the attribute with the same name in
+    /// the shader, so the layout doesn't depend on the `layout(location = ..)` of the shader. The
+    /// attributes that the shader doesn't declare are removed from the layout and reported as
+    /// unused. The missing attributes only consider this layout, use
+    /// `VertexArray::check_attribs` when the vertices come from several buffers
+    ///
+    /// # Example
+    ///
+    /// ``` Rust
+    /// let mut layout = MyVertex::layout();
+    /// let report = layout.resolve_locations(&shader);
+    /// if !report.is_ok() {
+    ///     println!("The layout doesn't match the shader {:?}", report);
+    /// }
+    /// vao.add_vertex_buffer(vbo, layout);
+    /// ```
+    pub fn resolve_locations(&mut self, shader: &Shader) -> AttribReport {
+        let report = AttribReport::compare(
+            shader,
+            self.attribs.iter().map(|attrib| attrib.name.as_str()),
+        );
+
+        self.attribs.retain_mut(|attrib| {
+            attrib.location = shader.get_attrib_location(&attrib.name);
+            attrib.location.is_some()
+        });
+
+        report
+    }
+}
+
+/// The differences between the attributes supplied by a layout and the ones declared by a shader
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AttribReport {
+    /// Attributes supplied by the layout that the shader doesn't declare
+    pub unused: Vec<String>,
+    /// Attributes declared by the shader that the layout doesn't supply
+    pub missing: Vec<String>,
+}
+
+impl AttribReport {
+    /// Return true if the layout and the shader match
+    pub fn is_ok(&self) -> bool {
+        self.unused.is_empty() && self.missing.is_empty()
+    }
+
+    fn compare<'a>(shader: &Shader, names: impl Iterator<Item = &'a str> + Clone) -> Self {
+        let active = shader.active_attribs();
+        Self {
+            unused: names
+                .clone()
+                .filter(|name| !active.iter().any(|attrib| attrib.name == *name))
+                .map(|name| name.to_string())
+                .collect(),
+            missing: active
+                .into_iter()
+                .filter(|attrib| !names.clone().any(|name| attrib.name == name))
+                .map(|attrib| attrib.name)
+                .collect(),
         }
     }
 }
 
+/// A type that can be used as vertex of a vertex buffer. Use `#[derive(Vertex)]` to implement
+/// it from the fields of a struct
+///
+/// A field with a explicit format that doesn't have the size of the format fails to compile
+///
+/// ```compile_fail
+/// use easy_opengl::buffers::Vertex;
+///
+/// #[repr(C)]
+/// #[derive(Vertex)]
+/// struct Particle {
+///     #[vertex(format = Half2)]
+///     uv: [u16; 3],
+/// }
+///
+/// Particle::layout();
+/// ```
+pub trait Vertex {
+    fn layout() -> VertexLayout;
+}
+
 /// Attach the vector of vertex attributes to a binded vertex array
 ///
 /// # Example
 /// ``` Rust
-///    let vertices = vec![
+///    let vertices: Vec<f32> = vec![
 ///        0.5, 0.5, 0.0, // top right
 ///        0.5, -0.5, 0.0, // bottom right
 ///        -0.5, -0.5, 0.0, // bottom left
@@ -116,7 +501,7 @@ impl VertexAttrib {
 ///        "pos".to_string(),
 ///    )]);
 /// ```
-pub fn submit_vertex_attribs(vertex_attribs: &mut Vec<VertexAttrib>) {
+pub fn submit_vertex_attribs(vertex_attribs: &mut [VertexAttrib]) {
     let mut stride = 0;
     let mut offset = 0;
     for attrib in vertex_attribs.iter_mut() {
@@ -125,37 +510,96 @@ pub fn submit_vertex_attribs(vertex_attribs: &mut Vec<VertexAttrib>) {
         stride += attrib.size;
     }
 
-    let mut i = 0;
+    submit_attribs(stride, vertex_attribs, 0);
+}
+
+/// Attach a vertex layout to a binded vertex array, the vertex buffer with the data have to be
+/// binded too
+///
+/// # Example
+/// ``` Rust
+///    let vao = VertexArray::new();
+///    let _vbo = VertexBuffer::from_slice(&vertices);
+///    vao.bind();
+///    submit_vertex_layout(&MyVertex::layout());
+/// ```
+pub fn submit_vertex_layout(layout: &VertexLayout) {
+    submit_attribs(layout.stride, &layout.attribs, 0);
+}
+
+/// Submit the attributes starting on `first_location`, return the location after the last one
+fn submit_attribs(stride: u32, vertex_attribs: &[VertexAttrib], first_location: u32) -> u32 {
+    let mut location = first_location;
     for attrib in vertex_attribs {
-        if vertex_attrib_type_gl(&attrib.vtype) == gl::FLOAT {
-            unsafe {
-                gl::VertexAttribPointer(
-                    i,
-                    vertex_attrib_type_count(&attrib.vtype) as i32,
-                    vertex_attrib_type_gl(&attrib.vtype),
-                    attrib.normalize as u8,
-                    stride as i32,
-                    attrib.offset as *const std::ffi::c_void,
-                );
+        if let Some(explicit) = attrib.location {
+            location = explicit;
+        }
+
+        // A matrix use one location for each column
+        let columns = vertex_attrib_type_locations(&attrib.vtype);
+        let count = vertex_attrib_type_count(&attrib.vtype) / columns;
+        let column_size = attrib.size / columns;
+
+        let gl_type = vertex_attrib_type_gl(&attrib.vtype);
+        for column in 0..columns {
+            let offset = (attrib.offset + column * column_size) as usize;
+
+            match gl_type {
+                gl::DOUBLE => unsafe {
+                    gl::VertexAttribLPointer(
+                        location,
+                        count as i32,
+                        gl_type,
+                        stride as i32,
+                        offset as *const std::ffi::c_void,
+                    );
+                },
+                gl::FLOAT
+                | gl::HALF_FLOAT
+                | gl::INT_2_10_10_10_REV
+                | gl::UNSIGNED_INT_2_10_10_10_REV
+                | gl::UNSIGNED_INT_10F_11F_11F_REV => unsafe {
+                    gl::VertexAttribPointer(
+                        location,
+                        count as i32,
+                        gl_type,
+                        attrib.normalize as u8,
+                        stride as i32,
+                        offset as *const std::ffi::c_void,
+                    );
+                },
+                // The normalized integers are read as floats by the shader
+                _ if attrib.normalize => unsafe {
+                    gl::VertexAttribPointer(
+                        location,
+                        count as i32,
+                        gl_type,
+                        gl::TRUE,
+                        stride as i32,
+                        offset as *const std::ffi::c_void,
+                    );
+                },
+                _ => unsafe {
+                    gl::VertexAttribIPointer(
+                        location,
+                        count as i32,
+                        gl_type,
+                        stride as i32,
+                        offset as *const std::ffi::c_void,
+                    );
+                },
             }
-        } else {
+
             unsafe {
-                gl::VertexAttribIPointer(
-                    i,
-                    vertex_attrib_type_count(&attrib.vtype) as i32,
-                    vertex_attrib_type_gl(&attrib.vtype),
-                    stride as i32,
-                    attrib.offset as *const std::ffi::c_void,
-                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, attrib.divisor);
             }
-        }
 
-        unsafe {
-            gl::EnableVertexAttribArray(i);
+            location += 1;
         }
-
-        i += 1;
     }
+
+    location
 }
 
 fn gen_vao() -> u32 {
@@ -170,46 +614,143 @@ fn gen_buffer() -> u32 {
     buffer
 }
 
-pub fn calc_bytes_size<T>(v: &Vec<T>) -> usize {
-    v.len() * std::mem::size_of::<T>()
+pub fn calc_bytes_size<T>(v: &[T]) -> usize {
+    std::mem::size_of_val(v)
+}
+
+/// A buffer object that can be shared, like the buffers referenced by a vertex array
+pub trait GpuBuffer {
+    fn raw(&self) -> &RawBuffer;
+}
+
+/// A index buffer with the type of the indices erased, it's how a vertex array keep its index
+/// buffer
+pub trait ElementBuffer: GpuBuffer {
+    fn index_type(&self) -> IndexType;
+    fn count(&self) -> usize;
+}
+
+/// A vertex buffer attached to a vertex array with the layout of its vertices
+struct VertexStream {
+    buffer: Rc<dyn GpuBuffer>,
+    layout: VertexLayout,
 }
 
-/// A abstract representation of a vertex array
+/// A abstract representation of a vertex array. It keeps alive the buffers attached to it, each
+/// vertex buffer is a stream with its own layout
 /// # Example
 /// ``` Rust
-///    let vertices = vec![
-///        0.5, 0.5, 0.0, // top right
-///        0.5, -0.5, 0.0, // bottom right
-///        -0.5, -0.5, 0.0, // bottom left
-///        -0.5, 0.5, 0.0, // top left
+///    let positions: Vec<[f32; 3]> = vec![
+///        [0.5, 0.5, 0.0], // top right
+///        [0.5, -0.5, 0.0], // bottom right
+///        [-0.5, -0.5, 0.0], // bottom left
+///        [-0.5, 0.5, 0.0], // top left
 ///    ];
-///    let indices = vec![
+///    let uvs: Vec<[f32; 2]> = vec![[1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]];
+///    let indices: Vec<u32> = vec![
 ///        0, 1, 3, // first Triangle
 ///        1, 2, 3, // second Triangle
 ///    ];
 ///
-///    let vao = VertexArray::new();
-///    let _vbo = VertexBuffer::new(calc_bytes_size(&vertices) as isize, Some(&vertices));
+///    let mut vao = VertexArray::new();
 ///
-///    vao.bind();
+///    // location 0
+///    vao.add_vertex_buffer(
+///        Rc::new(VertexBuffer::from_slice(&positions)),
+///        VertexLayout::packed(vec![VertexAttrib::new(
+///            VertexAttribType::Float3,
+///            false,
+///            "aPos".to_string(),
+///        )]),
+///    );
 ///
-///    submit_vertex_attribs(&mut vec![VertexAttrib::new(
-///        VertexAttribType::Float3,
-///        false,
-///        "pos".to_string(),
-///    )]);
+///    // location 1
+///    vao.add_vertex_buffer(
+///        Rc::new(VertexBuffer::from_slice(&uvs)),
+///        VertexLayout::packed(vec![VertexAttrib::new(
+///            VertexAttribType::Float2,
+///            false,
+///            "aTexCoord".to_string(),
+///        )]),
+///    );
 ///
-///    let _ibo = IndexBuffer::new(calc_bytes_size(&indices) as isize, Some(&indices));
+///    vao.set_index_buffer(Rc::new(IndexBuffer::from_slice(&indices)));
 /// ```
 pub struct VertexArray {
     pub id: u32,
+    streams: Vec<VertexStream>,
+    index_buffer: Option<Rc<dyn ElementBuffer>>,
+    next_location: u32,
+}
+
+impl Default for VertexArray {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VertexArray {
     /// Return a vertext array
-
     pub fn new() -> Self {
-        Self { id: gen_vao() }
+        Self {
+            id: gen_vao(),
+            streams: Vec::new(),
+            index_buffer: None,
+            next_location: 0,
+        }
+    }
+
+    /// Attach a vertex buffer to the vertex array, the attributes without a explicit location
+    /// continue after the last location used by the previous buffers. The vertex array keep a
+    /// reference to the buffer, so it isn't destroyed while the vertex array is alive
+    ///
+    ///  # Arguments
+    ///  * `buffer` - The buffer with the vertices, usually a `VertexBuffer`
+    ///  * `layout` - The stride and the attributes of the vertices of the buffer
+    pub fn add_vertex_buffer<B: GpuBuffer + 'static>(
+        &mut self,
+        buffer: Rc<B>,
+        layout: VertexLayout,
+    ) {
+        self.bind();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer.raw().id());
+        }
+        self.next_location = submit_attribs(layout.stride, &layout.attribs, self.next_location);
+        self.unbind();
+
+        self.streams.push(VertexStream { buffer, layout });
+    }
+
+    /// Attach a index buffer to the vertex array, it replace the previous one
+    pub fn set_index_buffer<T: IndexElement>(&mut self, buffer: Rc<IndexBuffer<T>>) {
+        self.bind();
+        buffer.bind();
+        self.unbind();
+
+        self.index_buffer = Some(buffer);
+    }
+
+    /// The index buffer attached to the vertex array
+    pub fn index_buffer(&self) -> Option<&dyn ElementBuffer> {
+        self.index_buffer.as_deref()
+    }
+
+    /// Compare the attributes supplied by all the vertex buffers of the vertex array with the
+    /// ones declared by the shader
+    pub fn check_attribs(&self, shader: &Shader) -> AttribReport {
+        AttribReport::compare(
+            shader,
+            self.streams
+                .iter()
+                .flat_map(|stream| stream.layout.attribs.iter())
+                .map(|attrib| attrib.name.as_str()),
+        )
+    }
+
+    /// The number of vertex buffers attached to the vertex array
+    pub fn vertex_buffer_count(&self) -> usize {
+        self.streams.len()
     }
 
     pub fn bind(&self) {
@@ -225,229 +766,1865 @@ impl VertexArray {
     }
 }
 
-/// A abstract representation of a vertex buffer
-///  # Example
-/// ``` Rust
-///    let vertices = vec![
-///        0.5, 0.5, 0.0, // top right
-///        0.5, -0.5, 0.0, // bottom right
-///        -0.5, -0.5, 0.0, // bottom left
-///        -0.5, 0.5, 0.0, // top left
-///    ];
-///
-///    // static
-///    let vbo1 = VertexBuffer::new(calc_bytes_size(&vertices) as isize, Some(&vertices));
-///
-///    // Dynamic
-///    let vbo2 = VertexBuffer::new(calc_bytes_size(&vertices) as isize);
-///
-///    // send half of the vertices
-///    vbo2.send_data(48 / 2, 0, vertices);
-/// ```
-pub struct VertexBuffer {
-    pub id: u32,
+/// Errors returned by the operations on the buffers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferError {
+    /// The range `offset..offset + len` doesn't fit in the `capacity` of the buffer. The values
+    /// are in elements of the buffer, bytes for untyped buffers
+    OutOfBounds {
+        offset: usize,
+        len: usize,
+        capacity: usize,
+    },
+    /// The buffer is already mapped
+    AlreadyMapped,
+    /// The driver couldn't map the buffer
+    MapFailed,
+    /// The offset in bytes isn't a multiple of the alignment of the mapped type
+    Misaligned { offset: usize, align: usize },
+    /// The access flags aren't valid for the mapping or for the storage of the buffer
+    InvalidMapFlags(&'static str),
+    /// The buffer has a immutable storage that can't be modified that way
+    ImmutableStorage,
+    /// The source and destination ranges of a copy inside the same buffer overlap
+    OverlappingCopy,
+    /// The range to bind doesn't have any element
+    EmptyRange,
 }
 
-impl VertexBuffer {
-    /// Return a VertexBuffer with the allocated size provided, the buffer data is static only if
-    /// the verticies isn't None, else, the buffer data is dynamic
-    ///
-    ///  # Arguments
-    ///  * `size` - The size in bytes of the data to allocate
-    ///  * `vertices` - A optional data to write
-    pub fn new<T>(size: isize, vertices: Option<&Vec<T>>) -> Self {
-        let _self = Self { id: gen_buffer() };
-        _self.bind();
-
-        if let Some(vertices) = vertices {
-            unsafe {
-                gl::BufferData(
-                    gl::ARRAY_BUFFER,
-                    size,
-                    vertices.as_ptr() as *const std::ffi::c_void,
-                    gl::STATIC_DRAW,
-                );
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferError::OutOfBounds {
+                offset,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "range {}..{} is out of the buffer bounds (capacity {})",
+                offset,
+                offset.saturating_add(*len),
+                capacity
+            ),
+            BufferError::AlreadyMapped => write!(f, "the buffer is already mapped"),
+            BufferError::MapFailed => write!(f, "the buffer couldn't be mapped"),
+            BufferError::Misaligned { offset, align } => {
+                write!(f, "offset {} isn't aligned to {} bytes", offset, align)
             }
-        } else {
-            unsafe {
-                gl::BufferData(gl::ARRAY_BUFFER, size, std::ptr::null(), gl::DYNAMIC_DRAW);
+            BufferError::InvalidMapFlags(reason) => write!(f, "invalid map flags: {}", reason),
+            BufferError::ImmutableStorage => {
+                write!(f, "the storage of the buffer is immutable")
+            }
+            BufferError::OverlappingCopy => {
+                write!(f, "the ranges of the copy overlap on the same buffer")
             }
+            BufferError::EmptyRange => write!(f, "the range to bind is empty"),
         }
-
-        _self
     }
+}
 
-    /// Write data that wasn't provided on the new function
-    ///
-    ///  # Arguments
-    ///  * `size` - The size in bytes of the data to write
-    ///  * `offset` - Point to a offset in the allocated space
-    ///  * `vertices` - Data to write
-    pub fn send_data<T>(&self, size: isize, offset: isize, vertices: &Vec<T>) {
-        unsafe {
-            self.bind();
-            gl::BufferSubData(
-                gl::ARRAY_BUFFER,
-                offset,
-                size,
-                vertices.as_ptr() as *const std::ffi::c_void,
-            );
-        }
-    }
+impl std::error::Error for BufferError {}
 
-    pub fn bind(&self) {
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
-        }
+fn check_bounds(offset: usize, len: usize, capacity: usize) -> Result<(), BufferError> {
+    match offset.checked_add(len) {
+        Some(end) if end <= capacity => Ok(()),
+        _ => Err(BufferError::OutOfBounds {
+            offset,
+            len,
+            capacity,
+        }),
     }
+}
 
-    pub fn unbind(&self) {
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-        }
-    }
+/// The number of elements of type `T` that fit in the biggest buffer that gl can allocate
+pub(crate) fn max_capacity<T>() -> usize {
+    isize::MAX as usize / size_of::<T>().max(1)
 }
 
-/// A abstract representation of a index buffer
-///  # Example
-/// ``` Rust
-///    let indices = vec![
-///        0, 1, 3, // first Triangle
-///        1, 2, 3, // second Triangle
-///    ];
-///
-///    // static
-///    let ibo1 = VertexBuffer::new(calc_bytes_size(&indices) as isize, Some(&vertices));
+/// The size in bytes of `capacity` elements of type `T`
+pub(crate) fn capacity_bytes<T>(capacity: usize) -> Result<usize, BufferError> {
+    capacity
+        .checked_mul(size_of::<T>())
+        .filter(|size| *size <= isize::MAX as usize)
+        .ok_or(BufferError::OutOfBounds {
+            offset: 0,
+            len: capacity,
+            capacity: max_capacity::<T>(),
+        })
+}
+
+/// The access flags of a buffer mapping, they can be combined with `|`
 ///
-///    // Dynamic
-///    let ibo2 = VertexBuffer::new(calc_bytes_size(&indices) as isize);
+/// # Example
 ///
-///    // send half of the vertices
-///    ibo2.send_data(24 / 2, 0, &indices);
+/// ``` Rust
+/// let flags = MapFlags::WRITE | MapFlags::INVALIDATE_RANGE;
 /// ```
-pub struct IndexBuffer {
-    pub id: u32,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapFlags(pub u32);
+
+impl MapFlags {
+    pub const READ: Self = Self(gl::MAP_READ_BIT);
+    pub const WRITE: Self = Self(gl::MAP_WRITE_BIT);
+    pub const INVALIDATE_RANGE: Self = Self(gl::MAP_INVALIDATE_RANGE_BIT);
+    pub const INVALIDATE_BUFFER: Self = Self(gl::MAP_INVALIDATE_BUFFER_BIT);
+    /// The modified ranges have to be flushed with `BufferMapping::flush`
+    pub const FLUSH_EXPLICIT: Self = Self(gl::MAP_FLUSH_EXPLICIT_BIT);
+    pub const UNSYNCHRONIZED: Self = Self(gl::MAP_UNSYNCHRONIZED_BIT);
+    /// The buffer can be used by the gpu while it's mapped, it needs a storage created with
+    /// `StorageFlags::MAP_PERSISTENT`
+    pub const PERSISTENT: Self = Self(gl::MAP_PERSISTENT_BIT);
+    /// The writes are visible to the gpu without flushing, it needs a storage created with
+    /// `StorageFlags::MAP_COHERENT`
+    pub const COHERENT: Self = Self(gl::MAP_COHERENT_BIT);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
 }
 
-impl IndexBuffer {
-    /// Return a IndexBuffer with the allocated size provided, the buffer data is static only if
-    /// the indices isn't None, else, the buffer data is dynamic
-    ///
-    ///  # Arguments
-    ///  * `size` - The size in bytes of the data to allocate
-    ///  * `indices` - A optional data to write
-    pub fn new(size: isize, indices: Option<&Vec<i32>>) -> Self {
-        let _self = Self { id: gen_buffer() };
-        _self.bind();
+impl std::ops::BitOr for MapFlags {
+    type Output = Self;
 
-        if let Some(indices) = indices {
-            unsafe {
-                gl::BufferData(
-                    gl::ELEMENT_ARRAY_BUFFER,
-                    size,
-                    indices.as_ptr() as *const std::ffi::c_void,
-                    gl::STATIC_DRAW,
-                );
-            }
-        } else {
-            unsafe {
-                gl::BufferData(
-                    gl::ELEMENT_ARRAY_BUFFER,
-                    size,
-                    std::ptr::null(),
-                    gl::DYNAMIC_DRAW,
-                );
-            }
-        }
-        _self
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
     }
+}
 
-    /// Write data that wasn't provided on the new function
-    ///
-    ///  # Arguments
-    ///  * `size` - The size in bytes of the data to write
-    ///  * `offset` - Point to a offset in the allocated space
-    ///  * `indices` - Data to write
-    pub fn send_data(&self, size: isize, offset: isize, indices: &Vec<i32>) {
-        unsafe {
-            self.bind();
-            gl::BufferSubData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                offset,
-                size,
-                indices.as_ptr() as *const std::ffi::c_void,
-            );
-        }
+/// The flags of a immutable buffer storage created with `glBufferStorage`, they can be combined
+/// with `|`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageFlags(pub u32);
+
+impl StorageFlags {
+    pub const NONE: Self = Self(0);
+    /// The data can be modified with `send_data`
+    pub const DYNAMIC_STORAGE: Self = Self(gl::DYNAMIC_STORAGE_BIT);
+    pub const MAP_READ: Self = Self(gl::MAP_READ_BIT);
+    pub const MAP_WRITE: Self = Self(gl::MAP_WRITE_BIT);
+    pub const MAP_PERSISTENT: Self = Self(gl::MAP_PERSISTENT_BIT);
+    pub const MAP_COHERENT: Self = Self(gl::MAP_COHERENT_BIT);
+    pub const CLIENT_STORAGE: Self = Self(gl::CLIENT_STORAGE_BIT);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for StorageFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
     }
+}
 
-    pub fn bind(&self) {
-        unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
+/// How the data of a buffer is going to be used, the mutable usages are hints given to
+/// `glBufferData` and `Immutable` creates a fixed size storage with `glBufferStorage`.
+///
+/// The first word is how often the data changes:
+/// * `Stream` - Written once and used a few times
+/// * `Static` - Written once and used many times
+/// * `Dynamic` - Written many times and used many times
+///
+/// The second word is who writes and reads the data:
+/// * `Draw` - Written by the application and read by the gpu
+/// * `Read` - Written by the gpu and read by the application
+/// * `Copy` - Written by the gpu and read by the gpu
+///
+/// # Example
+///
+/// ``` Rust
+/// let vbo = VertexBuffer::<[f32; 2]>::new(100, None, BufferUsage::DynamicDraw)?;
+/// let persistent = VertexBuffer::<[f32; 2]>::new(
+///     100,
+///     None,
+///     BufferUsage::Immutable(StorageFlags::MAP_WRITE | StorageFlags::MAP_PERSISTENT),
+/// )?;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+    StreamDraw,
+    StreamRead,
+    StreamCopy,
+    StaticDraw,
+    StaticRead,
+    StaticCopy,
+    DynamicDraw,
+    DynamicRead,
+    DynamicCopy,
+    /// A immutable storage, the size can't change and the flags decide how the data can be
+    /// modified and mapped
+    Immutable(StorageFlags),
+}
+
+impl BufferUsage {
+    /// The gl usage hint, like `gl::STATIC_DRAW`. None for `Immutable`
+    pub fn gl_hint(&self) -> Option<u32> {
+        match self {
+            BufferUsage::StreamDraw => Some(gl::STREAM_DRAW),
+            BufferUsage::StreamRead => Some(gl::STREAM_READ),
+            BufferUsage::StreamCopy => Some(gl::STREAM_COPY),
+            BufferUsage::StaticDraw => Some(gl::STATIC_DRAW),
+            BufferUsage::StaticRead => Some(gl::STATIC_READ),
+            BufferUsage::StaticCopy => Some(gl::STATIC_COPY),
+            BufferUsage::DynamicDraw => Some(gl::DYNAMIC_DRAW),
+            BufferUsage::DynamicRead => Some(gl::DYNAMIC_READ),
+            BufferUsage::DynamicCopy => Some(gl::DYNAMIC_COPY),
+            BufferUsage::Immutable(_) => None,
         }
     }
 
-    pub fn unbind(&self) {
-        unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+    /// The flags of the storage if it's immutable
+    pub fn storage_flags(&self) -> Option<StorageFlags> {
+        match self {
+            BufferUsage::Immutable(flags) => Some(*flags),
+            _ => None,
         }
     }
 }
 
-/// A abstract representation of a dynamic uniform buffer
-pub struct UniforBuffer {
-    pub id: u32,
-    pub slot: u32,
+/// The target used to create, write, read and map the buffers. `gl::ELEMENT_ARRAY_BUFFER` is a
+/// state of the binded vertex array, binding a index buffer to modify it would attach it to
+/// whatever vertex array was binded last
+const TRANSFER_TARGET: u32 = gl::COPY_WRITE_BUFFER;
+
+/// A untyped buffer object, it's the storage of the typed buffers. All the sizes and offsets are
+/// in bytes
+pub struct RawBuffer {
+    id: u32,
+    target: u32,
+    size: Cell<usize>,
+    usage: BufferUsage,
+    mapped: Cell<bool>,
 }
 
-impl UniforBuffer {
-    /// # Arguments
-    /// * `size` - Size in bytes of the buffer
-    /// * `binding` - The binding slot
-    pub fn new(size: isize, binding: u32) -> Self {
+impl RawBuffer {
+    /// Return a buffer of `size` bytes binded to `target`
+    ///
+    ///  # Arguments
+    ///  * `target` - The gl target, like `gl::ARRAY_BUFFER`
+    ///  * `size` - The size in bytes of the data to allocate
+    ///  * `data` - A optional data to write, it can't be bigger than the size
+    ///  * `usage` - How the data is going to be used
+    pub fn new(
+        target: u32,
+        size: usize,
+        data: Option<&[u8]>,
+        usage: BufferUsage,
+    ) -> Result<Self, BufferError> {
+        if let Some(data) = data {
+            check_bounds(0, data.len(), size)?;
+        }
+
         let _self = Self {
             id: gen_buffer(),
-            slot: binding,
+            target,
+            size: Cell::new(size),
+            usage,
+            mapped: Cell::new(false),
         };
-        unsafe {
-            gl::BufferData(gl::UNIFORM_BUFFER, size, std::ptr::null(), gl::DYNAMIC_DRAW);
-            gl::BindBufferBase(gl::UNIFORM_BUFFER, _self.slot, _self.id);
+        _self.bind_transfer();
+
+        match usage {
+            BufferUsage::Immutable(flags) => {
+                // The initial data has to be a full copy of the storage
+                let initial = data.map(|data| {
+                    let mut bytes = data.to_vec();
+                    bytes.resize(size, 0);
+                    bytes
+                });
+                unsafe {
+                    gl::BufferStorage(
+                        TRANSFER_TARGET,
+                        size as isize,
+                        initial
+                            .as_ref()
+                            .map_or(std::ptr::null(), |bytes| bytes.as_ptr() as *const c_void),
+                        flags.0,
+                    );
+                }
+            }
+            _ => {
+                unsafe {
+                    gl::BufferData(
+                        TRANSFER_TARGET,
+                        size as isize,
+                        std::ptr::null(),
+                        _self.gl_usage(),
+                    );
+                }
+                if let Some(data) = data {
+                    _self.send_bytes(0, data)?;
+                }
+            }
         }
 
-        _self
+        Ok(_self)
     }
 
-    /// # Arguments
-    /// * `data` - A void ptr to a array of data
-    /// * `size` - Size in bytes of the buffer
-    /// * `offset` - Offset pointing on the allocated data
-    pub fn send_data(&self, data: *const c_void, size: isize, offset: isize) {
-        unsafe {
-            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.slot, self.id);
-            gl::BufferSubData(gl::UNIFORM_BUFFER, offset, size, data);
-        }
+    pub fn id(&self) -> u32 {
+        self.id
     }
-}
 
-impl Drop for VertexArray {
-    fn drop(&mut self) {
-        unsafe { gl::DeleteVertexArrays(1, &self.id) }
+    pub fn target(&self) -> u32 {
+        self.target
     }
-}
 
-impl Drop for VertexBuffer {
+    /// The size in bytes of the buffer
+    pub fn size(&self) -> usize {
+        self.size.get()
+    }
+
+    /// How the data of the buffer is used
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    /// The flags of the storage if it's immutable
+    pub fn storage_flags(&self) -> Option<StorageFlags> {
+        self.usage.storage_flags()
+    }
+
+    /// Return true if the buffer is mapped
+    pub fn is_mapped(&self) -> bool {
+        self.mapped.get()
+    }
+
+    /// Write `data` starting at `offset` bytes
+    pub fn send_bytes(&self, offset: usize, data: &[u8]) -> Result<(), BufferError> {
+        check_bounds(offset, data.len(), self.size.get())?;
+        if let Some(storage) = self.storage_flags() {
+            if !storage.contains(StorageFlags::DYNAMIC_STORAGE) {
+                return Err(BufferError::ImmutableStorage);
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        unsafe {
+            self.bind_transfer();
+            gl::BufferSubData(
+                TRANSFER_TARGET,
+                offset as isize,
+                data.len() as isize,
+                data.as_ptr() as *const c_void,
+            );
+        }
+        Ok(())
+    }
+
+    /// Map `count` elements of type `T` starting at `offset` bytes, the buffer is unmapped when
+    /// the returned mapping is dropped
+    ///
+    ///  # Arguments
+    ///  * `offset` - The offset in bytes, it has to be aligned to the type
+    ///  * `count` - The number of elements to map
+    ///  * `flags` - The access flags, it needs at least `READ` or `WRITE`
+    pub fn map_range<T: Pod>(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        let size = count
+            .checked_mul(size_of::<T>())
+            .ok_or(BufferError::OutOfBounds {
+                offset,
+                len: count,
+                capacity: self.size.get(),
+            })?;
+        check_bounds(offset, size, self.size.get())?;
+        if !offset.is_multiple_of(std::mem::align_of::<T>()) {
+            return Err(BufferError::Misaligned {
+                offset,
+                align: std::mem::align_of::<T>(),
+            });
+        }
+        let ptr = if size == 0 {
+            self.check_map_flags(flags)?;
+            if self.mapped.get() {
+                return Err(BufferError::AlreadyMapped);
+            }
+            std::ptr::NonNull::<T>::dangling().as_ptr()
+        } else {
+            self.map_bytes(offset, size, flags)? as *mut T
+        };
+
+        Ok(BufferMapping {
+            buffer: self,
+            ptr,
+            len: count,
+            flags,
+        })
+    }
+
+    /// Map `size` bytes starting at `offset` and return the pointer to the mapped memory, the
+    /// caller is the responsible of call `unmap`
+    pub(crate) fn map_bytes(
+        &self,
+        offset: usize,
+        size: usize,
+        flags: MapFlags,
+    ) -> Result<*mut c_void, BufferError> {
+        check_bounds(offset, size, self.size.get())?;
+        self.check_map_flags(flags)?;
+        if self.mapped.get() {
+            return Err(BufferError::AlreadyMapped);
+        }
+
+        self.bind_transfer();
+        let ptr =
+            unsafe { gl::MapBufferRange(TRANSFER_TARGET, offset as isize, size as isize, flags.0) };
+        if ptr.is_null() {
+            return Err(BufferError::MapFailed);
+        }
+
+        self.mapped.set(true);
+        Ok(ptr)
+    }
+
+    pub(crate) fn unmap(&self) {
+        if self.mapped.get() {
+            self.bind_transfer();
+            unsafe {
+                gl::UnmapBuffer(TRANSFER_TARGET);
+            }
+            self.mapped.set(false);
+        }
+    }
+
+    /// Discard the content of the buffer allocating a new storage of the same size, the gpu can
+    /// keep using the old storage until it finish the pending draws. It can't be used on a
+    /// immutable storage
+    pub fn orphan(&self) -> Result<(), BufferError> {
+        if self.storage_flags().is_some() {
+            return Err(BufferError::ImmutableStorage);
+        }
+        if self.mapped.get() {
+            return Err(BufferError::AlreadyMapped);
+        }
+
+        self.bind_transfer();
+        unsafe {
+            gl::BufferData(
+                TRANSFER_TARGET,
+                self.size.get() as isize,
+                std::ptr::null(),
+                self.gl_usage(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Allocate a new storage of `size` bytes, the content of the buffer is lost. It can't be
+    /// used on a immutable storage
+    pub fn reallocate(&self, size: usize) -> Result<(), BufferError> {
+        if self.storage_flags().is_some() {
+            return Err(BufferError::ImmutableStorage);
+        }
+        if self.mapped.get() {
+            return Err(BufferError::AlreadyMapped);
+        }
+
+        self.bind_transfer();
+        unsafe {
+            gl::BufferData(
+                TRANSFER_TARGET,
+                size as isize,
+                std::ptr::null(),
+                self.gl_usage(),
+            );
+        }
+        self.size.set(size);
+        Ok(())
+    }
+
+    /// Allocate a new storage of `size` bytes keeping the content that fits in it. The gl name
+    /// of the buffer doesn't change, so the vertex arrays that use it stay valid. It can't be
+    /// used on a immutable storage
+    pub fn resize(&self, size: usize) -> Result<(), BufferError> {
+        if self.storage_flags().is_some() {
+            return Err(BufferError::ImmutableStorage);
+        }
+        if self.mapped.get() {
+            return Err(BufferError::AlreadyMapped);
+        }
+
+        let keep = size.min(self.size.get());
+        if keep == 0 {
+            return self.reallocate(size);
+        }
+
+        // Move the content out, allocate the new storage and move the content back, all on
+        // the gpu
+        let temp = RawBuffer::new(gl::COPY_WRITE_BUFFER, keep, None, BufferUsage::StreamCopy)?;
+        copy_buffer(self, 0, &temp, 0, keep)?;
+        self.reallocate(size)?;
+        copy_buffer(&temp, 0, self, 0, keep)
+    }
+
+    /// Copy the content of the buffer starting at `offset` bytes into `out`, it blocks until the
+    /// gpu finish writing the buffer
+    pub fn read_bytes(&self, offset: usize, out: &mut [u8]) -> Result<(), BufferError> {
+        check_bounds(offset, out.len(), self.size.get())?;
+        if self.mapped.get() {
+            return Err(BufferError::AlreadyMapped);
+        }
+        if out.is_empty() {
+            return Ok(());
+        }
+
+        self.bind_transfer();
+        unsafe {
+            gl::GetBufferSubData(
+                TRANSFER_TARGET,
+                offset as isize,
+                out.len() as isize,
+                out.as_mut_ptr() as *mut c_void,
+            );
+        }
+        Ok(())
+    }
+
+    /// The hint given to `glBufferData`, a immutable storage is never reallocated
+    fn gl_usage(&self) -> u32 {
+        self.usage.gl_hint().unwrap_or(gl::STATIC_DRAW)
+    }
+
+    fn check_map_flags(&self, flags: MapFlags) -> Result<(), BufferError> {
+        let read = flags.contains(MapFlags::READ);
+        let write = flags.contains(MapFlags::WRITE);
+        if !read && !write {
+            return Err(BufferError::InvalidMapFlags("READ or WRITE is required"));
+        }
+        if read
+            && (flags.contains(MapFlags::INVALIDATE_RANGE)
+                || flags.contains(MapFlags::INVALIDATE_BUFFER)
+                || flags.contains(MapFlags::UNSYNCHRONIZED))
+        {
+            return Err(BufferError::InvalidMapFlags(
+                "READ can't be used with INVALIDATE_* or UNSYNCHRONIZED",
+            ));
+        }
+        if flags.contains(MapFlags::FLUSH_EXPLICIT) && !write {
+            return Err(BufferError::InvalidMapFlags("FLUSH_EXPLICIT needs WRITE"));
+        }
+
+        let persistent = flags.contains(MapFlags::PERSISTENT);
+        let coherent = flags.contains(MapFlags::COHERENT);
+        if coherent && !persistent {
+            return Err(BufferError::InvalidMapFlags("COHERENT needs PERSISTENT"));
+        }
+
+        match self.storage_flags() {
+            None if persistent => Err(BufferError::InvalidMapFlags(
+                "PERSISTENT needs a immutable storage",
+            )),
+            None => Ok(()),
+            Some(storage) => {
+                let needed = [
+                    (read, StorageFlags::MAP_READ, "the storage hasn't MAP_READ"),
+                    (
+                        write,
+                        StorageFlags::MAP_WRITE,
+                        "the storage hasn't MAP_WRITE",
+                    ),
+                    (
+                        persistent,
+                        StorageFlags::MAP_PERSISTENT,
+                        "the storage hasn't MAP_PERSISTENT",
+                    ),
+                    (
+                        coherent,
+                        StorageFlags::MAP_COHERENT,
+                        "the storage hasn't MAP_COHERENT",
+                    ),
+                ];
+                for (used, flag, reason) in needed {
+                    if used && !storage.contains(flag) {
+                        return Err(BufferError::InvalidMapFlags(reason));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Bind the buffer to `TRANSFER_TARGET` to modify or read its storage without touching the
+    /// state of the binded vertex array
+    fn bind_transfer(&self) {
+        unsafe {
+            gl::BindBuffer(TRANSFER_TARGET, self.id);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, 0);
+        }
+    }
+}
+
+impl GpuBuffer for RawBuffer {
+    fn raw(&self) -> &RawBuffer {
+        self
+    }
+}
+
+/// Copy `len` bytes from `src` starting at `src_offset` to `dst` starting at `dst_offset`, the
+/// copy is done by the gpu without reading the data back. The ranges can be on the same buffer
+/// if they don't overlap
+///  # Example
+/// ``` Rust
+/// let positions = VertexBuffer::<[f32; 3]>::new(100, Some(&data), BufferUsage::StaticDraw)?;
+/// let backup = RawBuffer::new(gl::COPY_WRITE_BUFFER, 100 * 12, None, BufferUsage::StaticCopy)?;
+///
+/// // Copy the first 10 vertices
+/// copy_buffer(&positions, 0, &backup, 0, 10 * 12)?;
+/// ```
+pub fn copy_buffer<S: GpuBuffer + ?Sized, D: GpuBuffer + ?Sized>(
+    src: &S,
+    src_offset: usize,
+    dst: &D,
+    dst_offset: usize,
+    len: usize,
+) -> Result<(), BufferError> {
+    let (src, dst) = (src.raw(), dst.raw());
+    check_bounds(src_offset, len, src.size())?;
+    check_bounds(dst_offset, len, dst.size())?;
+    if src.is_mapped() || dst.is_mapped() {
+        return Err(BufferError::AlreadyMapped);
+    }
+    if src.id() == dst.id() && src_offset < dst_offset + len && dst_offset < src_offset + len {
+        return Err(BufferError::OverlappingCopy);
+    }
+    if len == 0 {
+        return Ok(());
+    }
+
+    unsafe {
+        gl::BindBuffer(gl::COPY_READ_BUFFER, src.id());
+        gl::BindBuffer(gl::COPY_WRITE_BUFFER, dst.id());
+        gl::CopyBufferSubData(
+            gl::COPY_READ_BUFFER,
+            gl::COPY_WRITE_BUFFER,
+            src_offset as isize,
+            dst_offset as isize,
+            len as isize,
+        );
+    }
+    Ok(())
+}
+
+/// A mapped range of a buffer seen as a slice of `T`, the buffer is unmapped when it's dropped
+///
+/// # Example
+///
+/// ``` Rust
+/// let vbo = VertexBuffer::<[f32; 2]>::new(100, None, BufferUsage::DynamicDraw)?;
+/// {
+///     let mut vertices = vbo.map_range(0, 100, MapFlags::WRITE | MapFlags::INVALIDATE_RANGE)?;
+///     for (i, vertex) in vertices.iter_mut().enumerate() {
+///         *vertex = [i as f32, 0.0];
+///     }
+/// } // unmapped here
+/// ```
+pub struct BufferMapping<'a, T: Pod> {
+    buffer: &'a RawBuffer,
+    ptr: *mut T,
+    len: usize,
+    flags: MapFlags,
+}
+
+impl<T: Pod> BufferMapping<'_, T> {
+    /// The access flags used to map the buffer
+    pub fn flags(&self) -> MapFlags {
+        self.flags
+    }
+
+    /// Make visible to the gpu the writes on the range of elements, relative to the start of the
+    /// mapping. The mapping needs the `FLUSH_EXPLICIT` flag
+    pub fn flush(&self, range: std::ops::Range<usize>) -> Result<(), BufferError> {
+        if !self.flags.contains(MapFlags::FLUSH_EXPLICIT) {
+            return Err(BufferError::InvalidMapFlags("flush needs FLUSH_EXPLICIT"));
+        }
+        let len = range.end.saturating_sub(range.start);
+        check_bounds(range.start, len, self.len)?;
+        if len == 0 {
+            return Ok(());
+        }
+
+        self.buffer.bind_transfer();
+        unsafe {
+            gl::FlushMappedBufferRange(
+                TRANSFER_TARGET,
+                (range.start * size_of::<T>()) as isize,
+                (len * size_of::<T>()) as isize,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<T: Pod> std::ops::Deref for BufferMapping<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T: Pod> std::ops::DerefMut for BufferMapping<'_, T> {
+    /// Panics if the mapping hasn't the `WRITE` flag
+    fn deref_mut(&mut self) -> &mut [T] {
+        assert!(
+            self.flags.contains(MapFlags::WRITE),
+            "the buffer wasn't mapped with MapFlags::WRITE"
+        );
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<T: Pod> Drop for BufferMapping<'_, T> {
     fn drop(&mut self) {
-        unsafe { gl::DeleteBuffers(1, &self.id) }
+        // A empty mapping doesn't map the buffer
+        if self.len * size_of::<T>() != 0 {
+            self.buffer.unmap();
+        }
+    }
+}
+
+/// A abstract representation of a vertex buffer, it knows the type and the number of the
+/// vertices that it can hold
+///  # Example
+/// ``` Rust
+///    let vertices: Vec<f32> = vec![
+///        0.5, 0.5, 0.0, // top right
+///        0.5, -0.5, 0.0, // bottom right
+///        -0.5, -0.5, 0.0, // bottom left
+///        -0.5, 0.5, 0.0, // top left
+///    ];
+///
+///    // static
+///    let vbo1 = VertexBuffer::from_slice(&vertices);
+///
+///    // Dynamic
+///    let vbo2 = VertexBuffer::<f32>::new(vertices.len(), None, BufferUsage::DynamicDraw)?;
+///
+///    // send the second half of the vertices
+///    vbo2.send_data(6, &vertices[6..])?;
+/// ```
+pub struct VertexBuffer<T: Pod> {
+    raw: RawBuffer,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> VertexBuffer<T> {
+    /// Return a VertexBuffer with space for `capacity` vertices
+    ///
+    ///  # Arguments
+    ///  * `capacity` - The number of vertices to allocate
+    ///  * `vertices` - A optional data to write, it can't be longer than the capacity
+    ///  * `usage` - How the data is going to be used
+    pub fn new(
+        capacity: usize,
+        vertices: Option<&[T]>,
+        usage: BufferUsage,
+    ) -> Result<Self, BufferError> {
+        if let Some(vertices) = vertices {
+            check_bounds(0, vertices.len(), capacity)?;
+        }
+
+        let raw = RawBuffer::new(
+            gl::ARRAY_BUFFER,
+            capacity_bytes::<T>(capacity)?,
+            vertices.map(bytemuck::cast_slice),
+            usage,
+        )?;
+
+        Ok(Self {
+            raw,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Return a `StaticDraw` VertexBuffer with the exact size of the vertices
+    pub fn from_slice(vertices: &[T]) -> Self {
+        Self::new(vertices.len(), Some(vertices), BufferUsage::StaticDraw).unwrap()
+    }
+
+    /// Write data that wasn't provided on the new function
+    ///
+    ///  # Arguments
+    ///  * `offset` - The index of the first vertex to write
+    ///  * `vertices` - Data to write
+    pub fn send_data(&self, offset: usize, vertices: &[T]) -> Result<(), BufferError> {
+        check_bounds(offset, vertices.len(), self.capacity())?;
+        self.raw
+            .send_bytes(offset * size_of::<T>(), bytemuck::cast_slice(vertices))
+    }
+
+    /// Read `count` vertices starting at the vertex `offset`, it blocks until the gpu finish
+    /// writing the buffer
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        let mut data = vec![T::zeroed(); count];
+        self.raw
+            .read_bytes(offset * size_of::<T>(), bytemuck::cast_slice_mut(&mut data))?;
+        Ok(data)
+    }
+
+    /// Map `count` vertices starting at the vertex `offset`, see `RawBuffer::map_range`
+    pub fn map_range(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        self.raw.map_range(offset * size_of::<T>(), count, flags)
+    }
+
+    /// Allocate space for `capacity` vertices keeping the vertices that fit, the vertex arrays
+    /// that use the buffer stay valid. See `RawBuffer::resize`
+    pub fn resize(&self, capacity: usize) -> Result<(), BufferError> {
+        self.raw.resize(capacity_bytes::<T>(capacity)?)
+    }
+
+    /// Make sure that the buffer can hold at least `capacity` vertices, the buffer grows at
+    /// least to the double of its capacity so calling it for each new vertex is cheap
+    pub fn reserve(&self, capacity: usize) -> Result<(), BufferError> {
+        if capacity > self.capacity() {
+            let grown = self.capacity().saturating_mul(2).min(max_capacity::<T>());
+            self.resize(capacity.max(grown))?;
+        }
+        Ok(())
+    }
+
+    /// The number of vertices that the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.raw.size() / size_of::<T>().max(1)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    pub fn bind(&self) {
+        self.raw.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.raw.unbind();
+    }
+}
+
+impl<T: Pod> GpuBuffer for VertexBuffer<T> {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+/// The type of the indices of a index buffer, the value is the gl enum used on the draw calls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+    U8 = gl::UNSIGNED_BYTE as isize,
+    U16 = gl::UNSIGNED_SHORT as isize,
+    U32 = gl::UNSIGNED_INT as isize,
+}
+
+impl IndexType {
+    /// The size in bytes of one index
+    pub fn size(&self) -> usize {
+        match self {
+            IndexType::U8 => 1,
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for super::DrawArraysIndirectCommand {}
+    impl Sealed for super::DrawElementsIndirectCommand {}
+}
+
+/// A type that can be used as index of a index buffer, it's implemented for `u8`, `u16` and `u32`
+pub trait IndexElement: Pod + sealed::Sealed {
+    const INDEX_TYPE: IndexType;
+}
+
+impl IndexElement for u8 {
+    const INDEX_TYPE: IndexType = IndexType::U8;
+}
+
+impl IndexElement for u16 {
+    const INDEX_TYPE: IndexType = IndexType::U16;
+}
+
+impl IndexElement for u32 {
+    const INDEX_TYPE: IndexType = IndexType::U32;
+}
+
+/// A abstract representation of a index buffer, it knows the type of the indices, how many it can
+/// hold and how many are written
+///  # Example
+/// ``` Rust
+///    let indices: Vec<u16> = vec![
+///        0, 1, 3, // first Triangle
+///        1, 2, 3, // second Triangle
+///    ];
+///
+///    // static
+///    let ibo1 = IndexBuffer::from_slice(&indices);
+///
+///    // Dynamic
+///    let ibo2 = IndexBuffer::<u16>::new(indices.len(), None, BufferUsage::DynamicDraw)?;
+///
+///    // send the first triangle
+///    ibo2.send_data(0, &indices[..3])?;
+///
+///    vao.bind();
+///    gl::DrawElements(
+///        gl::TRIANGLES,
+///        ibo2.count() as i32, // 3
+///        ibo2.index_type() as u32, // gl::UNSIGNED_SHORT
+///        std::ptr::null(),
+///    );
+/// ```
+pub struct IndexBuffer<T: IndexElement = u32> {
+    raw: RawBuffer,
+    count: Cell<usize>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: IndexElement> IndexBuffer<T> {
+    /// Return a IndexBuffer with space for `capacity` indices
+    ///
+    ///  # Arguments
+    ///  * `capacity` - The number of indices to allocate
+    ///  * `indices` - A optional data to write, it can't be longer than the capacity
+    ///  * `usage` - How the data is going to be used
+    pub fn new(
+        capacity: usize,
+        indices: Option<&[T]>,
+        usage: BufferUsage,
+    ) -> Result<Self, BufferError> {
+        if let Some(indices) = indices {
+            check_bounds(0, indices.len(), capacity)?;
+        }
+
+        let raw = RawBuffer::new(
+            gl::ELEMENT_ARRAY_BUFFER,
+            capacity_bytes::<T>(capacity)?,
+            indices.map(bytemuck::cast_slice),
+            usage,
+        )?;
+
+        Ok(Self {
+            raw,
+            count: Cell::new(indices.map_or(0, |indices| indices.len())),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Return a `StaticDraw` IndexBuffer with the exact size of the indices
+    pub fn from_slice(indices: &[T]) -> Self {
+        Self::new(indices.len(), Some(indices), BufferUsage::StaticDraw).unwrap()
+    }
+
+    /// Write data that wasn't provided on the new function, the count grows to include the
+    /// written indices
+    ///
+    ///  # Arguments
+    ///  * `offset` - The position of the first index to write
+    ///  * `indices` - Data to write
+    pub fn send_data(&self, offset: usize, indices: &[T]) -> Result<(), BufferError> {
+        check_bounds(offset, indices.len(), self.capacity())?;
+        self.raw
+            .send_bytes(offset * size_of::<T>(), bytemuck::cast_slice(indices))?;
+        self.count.set(self.count.get().max(offset + indices.len()));
+        Ok(())
+    }
+
+    /// Read `count` indices starting at the index `offset`, it blocks until the gpu finish
+    /// writing the buffer
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        let mut data = vec![T::zeroed(); count];
+        self.raw
+            .read_bytes(offset * size_of::<T>(), bytemuck::cast_slice_mut(&mut data))?;
+        Ok(data)
+    }
+
+    /// Map `count` indices starting at the index `offset`, see `RawBuffer::map_range`. The writes
+    /// through the mapping don't change the count, use `set_count`
+    pub fn map_range(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        self.raw.map_range(offset * size_of::<T>(), count, flags)
+    }
+
+    /// The type of the indices, use it on the draw calls
+    pub fn index_type(&self) -> IndexType {
+        T::INDEX_TYPE
+    }
+
+    /// The number of indices written on the buffer
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    /// Set the number of valid indices, useful to draw only a part of a dynamic buffer
+    pub fn set_count(&self, count: usize) -> Result<(), BufferError> {
+        check_bounds(0, count, self.capacity())?;
+        self.count.set(count);
+        Ok(())
+    }
+
+    /// Allocate space for `capacity` indices keeping the indices that fit, the count is clamped
+    /// to the new capacity. The vertex arrays that use the buffer stay valid, see
+    /// `RawBuffer::resize`
+    pub fn resize(&self, capacity: usize) -> Result<(), BufferError> {
+        self.raw.resize(capacity_bytes::<T>(capacity)?)?;
+        self.count.set(self.count.get().min(capacity));
+        Ok(())
+    }
+
+    /// Make sure that the buffer can hold at least `capacity` indices, the buffer grows at
+    /// least to the double of its capacity so calling it for each new index is cheap
+    pub fn reserve(&self, capacity: usize) -> Result<(), BufferError> {
+        if capacity > self.capacity() {
+            let grown = self.capacity().saturating_mul(2).min(max_capacity::<T>());
+            self.resize(capacity.max(grown))?;
+        }
+        Ok(())
+    }
+
+    /// The number of indices that the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.raw.size() / size_of::<T>().max(1)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    pub fn bind(&self) {
+        self.raw.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.raw.unbind();
+    }
+}
+
+impl<T: IndexElement> GpuBuffer for IndexBuffer<T> {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+impl<T: IndexElement> ElementBuffer for IndexBuffer<T> {
+    fn index_type(&self) -> IndexType {
+        T::INDEX_TYPE
+    }
+
+    fn count(&self) -> usize {
+        self.count.get()
+    }
+}
+
+/// The parameters of a `glDrawArraysIndirect`, the layout is the one expected by gl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct DrawArraysIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first: u32,
+    pub base_instance: u32,
+}
+
+impl DrawArraysIndirectCommand {
+    /// Return a command that draws one instance of the vertices on `vertices`
+    pub fn new(vertices: std::ops::Range<u32>) -> Self {
+        Self {
+            count: vertices.end.saturating_sub(vertices.start),
+            instance_count: 1,
+            first: vertices.start,
+            base_instance: 0,
+        }
+    }
+
+    pub fn with_instances(mut self, instances: u32) -> Self {
+        self.instance_count = instances;
+        self
+    }
+
+    pub fn with_base_instance(mut self, base_instance: u32) -> Self {
+        self.base_instance = base_instance;
+        self
+    }
+}
+
+/// The parameters of a `glDrawElementsIndirect`, the layout is the one expected by gl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct DrawElementsIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+impl DrawElementsIndirectCommand {
+    /// Return a command that draws one instance of the indices on `indices`, the positions of
+    /// the index buffer of the vertex array
+    pub fn new(indices: std::ops::Range<u32>) -> Self {
+        Self {
+            count: indices.end.saturating_sub(indices.start),
+            instance_count: 1,
+            first_index: indices.start,
+            base_vertex: 0,
+            base_instance: 0,
+        }
+    }
+
+    pub fn with_instances(mut self, instances: u32) -> Self {
+        self.instance_count = instances;
+        self
+    }
+
+    /// Add `base_vertex` to each index before fetching the vertex
+    pub fn with_base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+
+    pub fn with_base_instance(mut self, base_instance: u32) -> Self {
+        self.base_instance = base_instance;
+        self
+    }
+}
+
+/// A command of a indirect draw, it's implemented for `DrawArraysIndirectCommand` and
+/// `DrawElementsIndirectCommand`
+pub trait IndirectCommand: Pod + sealed::Sealed {
+    /// True if the command draws with the index buffer of the vertex array
+    const INDEXED: bool;
+}
+
+impl IndirectCommand for DrawArraysIndirectCommand {
+    const INDEXED: bool = false;
+}
+
+impl IndirectCommand for DrawElementsIndirectCommand {
+    const INDEXED: bool = true;
+}
+
+/// A abstract representation of a buffer of indirect draw commands. The commands can be
+/// appended on the cpu with `push` and sent together with `upload`, the buffer grows when they
+/// don't fit. See the functions of `draw` to draw with it
+///  # Example
+/// ``` Rust
+/// let commands = DrawIndirectBuffer::<DrawElementsIndirectCommand>::new(
+///     1024,
+///     BufferUsage::DynamicDraw,
+/// )?;
+///
+/// for mesh in &meshes {
+///     commands.push(
+///         DrawElementsIndirectCommand::new(mesh.first_index..mesh.first_index + mesh.count)
+///             .with_base_vertex(mesh.base_vertex),
+///     );
+/// }
+/// commands.upload()?;
+///
+/// multi_draw_indirect(&vao, Topology::Triangles, &commands, 0..commands.count())?;
+/// ```
+pub struct DrawIndirectBuffer<T: IndirectCommand> {
+    raw: RawBuffer,
+    count: Cell<usize>,
+    pending: RefCell<Vec<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: IndirectCommand> DrawIndirectBuffer<T> {
+    /// Return a DrawIndirectBuffer with space for `capacity` commands
+    ///
+    ///  # Arguments
+    ///  * `capacity` - The number of commands to allocate
+    ///  * `usage` - How the data is going to be used
+    pub fn new(capacity: usize, usage: BufferUsage) -> Result<Self, BufferError> {
+        Ok(Self {
+            raw: RawBuffer::new(
+                gl::DRAW_INDIRECT_BUFFER,
+                capacity_bytes::<T>(capacity)?,
+                None,
+                usage,
+            )?,
+            count: Cell::new(0),
+            pending: RefCell::new(Vec::new()),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Return a `StaticDraw` DrawIndirectBuffer with the exact size of the commands
+    pub fn from_slice(commands: &[T]) -> Self {
+        let _self = Self::new(commands.len(), BufferUsage::StaticDraw).unwrap();
+        _self.send_data(0, commands).unwrap();
+        _self
+    }
+
+    /// Append a command on the cpu, it's sent to the buffer on the next `upload`. Return the
+    /// position that the command will have on the buffer
+    pub fn push(&self, command: T) -> usize {
+        let mut pending = self.pending.borrow_mut();
+        pending.push(command);
+        self.count.get() + pending.len() - 1
+    }
+
+    /// Send the pushed commands after the commands already on the buffer, the buffer grows if
+    /// they don't fit
+    pub fn upload(&self) -> Result<(), BufferError> {
+        let mut pending = self.pending.borrow_mut();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let count = self.count.get();
+        let needed = count
+            .checked_add(pending.len())
+            .ok_or(BufferError::OutOfBounds {
+                offset: count,
+                len: pending.len(),
+                capacity: self.capacity(),
+            })?;
+        if needed > self.capacity() {
+            let grown = self.capacity().saturating_mul(2).min(max_capacity::<T>());
+            self.raw.resize(capacity_bytes::<T>(needed.max(grown))?)?;
+        }
+        self.raw
+            .send_bytes(count * size_of::<T>(), bytemuck::cast_slice(&pending))?;
+        self.count.set(count + pending.len());
+        pending.clear();
+        Ok(())
+    }
+
+    /// Remove all the commands, the pushed and the sent ones. The storage is kept
+    pub fn clear(&self) {
+        self.pending.borrow_mut().clear();
+        self.count.set(0);
+    }
+
+    /// Write commands starting at the command `offset`, the count grows to include them
+    pub fn send_data(&self, offset: usize, commands: &[T]) -> Result<(), BufferError> {
+        check_bounds(offset, commands.len(), self.capacity())?;
+        self.raw
+            .send_bytes(offset * size_of::<T>(), bytemuck::cast_slice(commands))?;
+        self.count
+            .set(self.count.get().max(offset + commands.len()));
+        Ok(())
+    }
+
+    /// Read `count` commands starting at the command `offset`, it blocks until the gpu finish
+    /// writing the buffer
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        let mut data = vec![T::zeroed(); count];
+        self.raw
+            .read_bytes(offset * size_of::<T>(), bytemuck::cast_slice_mut(&mut data))?;
+        Ok(data)
+    }
+
+    /// Map `count` commands starting at the command `offset`, see `RawBuffer::map_range`. The
+    /// writes through the mapping don't change the count, use `set_count`
+    pub fn map_range(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        self.raw.map_range(offset * size_of::<T>(), count, flags)
+    }
+
+    /// The number of commands sent to the buffer, the pushed commands count after `upload`
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    /// Set the number of valid commands, useful when the commands are written by the gpu
+    pub fn set_count(&self, count: usize) -> Result<(), BufferError> {
+        check_bounds(0, count, self.capacity())?;
+        self.count.set(count);
+        Ok(())
+    }
+
+    /// The number of commands that the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.raw.size() / size_of::<T>()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    pub fn bind(&self) {
+        self.raw.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.raw.unbind();
+    }
+}
+
+impl<T: IndirectCommand> GpuBuffer for DrawIndirectBuffer<T> {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+/// A abstract representation of a pixel buffer, the storage of the pixels transferred between
+/// a texture and the cpu without stalling. A upload copies the pixels from the buffer to the
+/// texture and a readback copies the pixels of the texture to the buffer, both are done by the
+/// gpu after the call returns. A fence is inserted after each transfer, so `is_ready` tells
+/// without blocking when the buffer can be written again or the pixels can be read.
+///
+/// The rows of the pixels are aligned to 4 bytes, the default alignment of gl
+///  # Example
+/// ``` Rust
+/// // Upload a video frame
+/// let upload = PixelBuffer::new(width * height * 4, BufferUsage::StreamDraw)?;
+/// if upload.is_ready() {
+///     upload.send_data(0, &frame)?;
+///     texture.send_from_buffer(0, 0, width, height, &upload, 0)?;
+/// }
+///
+/// // Capture a frame, the pixels are read some frames later
+/// let capture = PixelBuffer::new(width * height * 4, BufferUsage::StreamRead)?;
+/// texture.read_into_buffer(&capture, 0)?;
+/// // ...
+/// if capture.is_ready() {
+///     let pixels = capture.read_range(0, capture.size())?;
+/// }
+/// ```
+pub struct PixelBuffer {
+    raw: RawBuffer,
+    fence: RefCell<Option<Fence>>,
+}
+
+impl PixelBuffer {
+    /// Return a PixelBuffer of `size` bytes
+    ///
+    ///  # Arguments
+    ///  * `size` - The size in bytes of the pixels to allocate
+    ///  * `usage` - How the data is going to be used, usually `StreamDraw` for uploads and
+    ///    `StreamRead` for readbacks
+    pub fn new(size: usize, usage: BufferUsage) -> Result<Self, BufferError> {
+        // The pack and unpack targets change how the texture functions read the pointers, so the
+        // buffer is only binded to them during the transfers
+        Ok(Self {
+            raw: RawBuffer::new(gl::COPY_WRITE_BUFFER, size, None, usage)?,
+            fence: RefCell::new(None),
+        })
+    }
+
+    /// Write pixels starting at `offset` bytes, it doesn't wait for the previous transfer
+    pub fn send_data(&self, offset: usize, data: &[u8]) -> Result<(), BufferError> {
+        self.raw.send_bytes(offset, data)
+    }
+
+    /// Read `count` bytes starting at `offset` bytes, it blocks until the previous transfer
+    /// finish. Call it when `is_ready` to don't stall
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<u8>, BufferError> {
+        check_bounds(offset, count, self.raw.size())?;
+        let mut data = vec![0; count];
+        self.raw.read_bytes(offset, &mut data)?;
+        Ok(data)
+    }
+
+    /// Map `count` bytes starting at `offset` bytes, see `RawBuffer::map_range`
+    pub fn map_range(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, u8>, BufferError> {
+        self.raw.map_range(offset, count, flags)
+    }
+
+    /// Return true if the gpu finished the last transfer, it never blocks
+    pub fn is_ready(&self) -> bool {
+        let mut fence = self.fence.borrow_mut();
+        if fence.as_ref().is_some_and(|fence| fence.is_signaled()) {
+            *fence = None;
+        }
+        fence.is_none()
+    }
+
+    /// Block until the gpu finish the last transfer or the timeout expires, return true if the
+    /// transfer finished
+    ///
+    ///  # Arguments
+    ///  * `timeout` - The max time to wait in nanoseconds
+    pub fn wait(&self, timeout: u64) -> bool {
+        let mut fence = self.fence.borrow_mut();
+        if fence.as_ref().is_some_and(|fence| fence.wait(timeout)) {
+            *fence = None;
+        }
+        fence.is_none()
+    }
+
+    /// Bind the buffer to `target` to run a transfer, the fence of the transfer is inserted
+    /// when `transfer` returns
+    pub(crate) fn transfer<R>(&self, target: u32, transfer: impl FnOnce() -> R) -> R {
+        unsafe {
+            gl::BindBuffer(target, self.raw.id());
+        }
+        let result = transfer();
+        unsafe {
+            gl::BindBuffer(target, 0);
+        }
+        self.fence.replace(Some(Fence::new()));
+        result
+    }
+
+    /// The size in bytes of the buffer
+    pub fn size(&self) -> usize {
+        self.raw.size()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+impl GpuBuffer for PixelBuffer {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+/// A abstract representation of a uniform buffer
+pub struct UniforBuffer {
+    raw: RawBuffer,
+    pub slot: u32,
+}
+
+impl UniforBuffer {
+    /// # Arguments
+    /// * `size` - Size in bytes of the buffer
+    /// * `binding` - The binding slot
+    /// * `usage` - How the data is going to be used, usually `DynamicDraw`
+    pub fn new(size: isize, binding: u32, usage: BufferUsage) -> Self {
+        let _self = Self {
+            raw: RawBuffer::new(gl::UNIFORM_BUFFER, size as usize, None, usage).unwrap(),
+            slot: binding,
+        };
+        _self.bind_base();
+
+        _self
+    }
+
+    /// # Arguments
+    /// * `data` - A void ptr to a array of data
+    /// * `size` - Size in bytes of the buffer
+    /// * `offset` - Offset pointing on the allocated data
+    ///
+    /// # Safety
+    /// `data` has to point to at least `size` readable bytes. Use `send_bytes` or `send_block`
+    /// to write without unsafe code
+    pub unsafe fn send_data(&self, data: *const c_void, size: isize, offset: isize) {
+        unsafe {
+            self.bind_base();
+            gl::BufferSubData(gl::UNIFORM_BUFFER, offset, size, data);
+        }
+    }
+
+    /// Write `data` starting at `offset` bytes
+    pub fn send_bytes(&self, offset: usize, data: &[u8]) -> Result<(), BufferError> {
+        self.bind_base();
+        self.raw.send_bytes(offset, data)
+    }
+
+    /// Return a uniform buffer with the size of the `std140` layout of `T`
+    ///
+    /// # Arguments
+    /// * `binding` - The binding slot
+    /// * `usage` - How the data is going to be used, usually `DynamicDraw`
+    pub fn for_block<T: Std140>(binding: u32, usage: BufferUsage) -> Self {
+        Self::new(T::STD140_SIZE as isize, binding, usage)
+    }
+
+    /// Write the block with the `std140` layout starting at `offset` bytes
+    pub fn send_block<T: Std140>(&self, offset: usize, block: &T) -> Result<(), BufferError> {
+        self.bind_base();
+        self.raw.send_bytes(offset, &block.to_std140_bytes())
+    }
+
+    /// Read `count` elements of type `T` starting at `offset` bytes, it blocks until the gpu
+    /// finish writing the buffer
+    pub fn read_range<T: Pod>(&self, offset: usize, count: usize) -> Result<Vec<T>, BufferError> {
+        let size = count
+            .checked_mul(size_of::<T>())
+            .ok_or(BufferError::OutOfBounds {
+                offset,
+                len: count,
+                capacity: self.raw.size(),
+            })?;
+        check_bounds(offset, size, self.raw.size())?;
+        let mut data = vec![T::zeroed(); count];
+        self.raw
+            .read_bytes(offset, bytemuck::cast_slice_mut(&mut data))?;
+        Ok(data)
+    }
+
+    /// Map `count` elements of type `T` starting at `offset` bytes, see `RawBuffer::map_range`
+    pub fn map_range<T: Pod>(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        self.raw.map_range(offset, count, flags)
+    }
+
+    /// Allocate `size` bytes keeping the content that fits, see `RawBuffer::resize`. The buffer
+    /// stays binded to its slot
+    pub fn resize(&self, size: usize) -> Result<(), BufferError> {
+        self.raw.resize(size)?;
+        self.bind_base();
+        Ok(())
+    }
+
+    /// Make sure that the buffer can hold at least `size` bytes, the buffer grows at least to
+    /// the double of its size
+    pub fn reserve(&self, size: usize) -> Result<(), BufferError> {
+        if size > self.size() {
+            self.resize(size.max(self.size() * 2))?;
+        }
+        Ok(())
+    }
+
+    /// The size in bytes of the buffer
+    pub fn size(&self) -> usize {
+        self.raw.size()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    /// Bind the buffer to its binding slot
+    pub fn bind_base(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.slot, self.raw.id());
+        }
+    }
+}
+
+impl GpuBuffer for UniforBuffer {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+/// A abstract representation of a shader storage buffer, a array of `T` that the shaders can
+/// read and write. It's binded to a binding slot like the uniform buffers.
+///
+/// The elements are written with the rust layout of `T`, that only matches `std430` when `T`
+/// hasn't padding on GLSL. A `vec3[]` has a stride of 16 bytes, so use `[f32; 4]` as element or
+/// write the data with `send_block`
+///  # Example
+/// ``` Rust
+/// // GLSL: layout(std430, binding = 2) buffer Particles { vec4 positions[]; };
+/// let particles =
+///     StorageBuffer::<[f32; 4]>::new(1024, Some(&positions), 2, BufferUsage::DynamicCopy)?;
+///
+/// compute_shader.bind();
+/// gl::DispatchCompute(1024 / 64, 1, 1);
+/// gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+///
+/// let first_ten = particles.read_range(0, 10)?;
+/// ```
+pub struct StorageBuffer<T: Pod> {
+    raw: RawBuffer,
+    pub slot: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> StorageBuffer<T> {
+    /// Return a StorageBuffer with space for `capacity` elements binded to the slot `binding`
+    ///
+    ///  # Arguments
+    ///  * `capacity` - The number of elements to allocate
+    ///  * `data` - A optional data to write, it can't be longer than the capacity
+    ///  * `binding` - The binding slot
+    ///  * `usage` - How the data is going to be used
+    pub fn new(
+        capacity: usize,
+        data: Option<&[T]>,
+        binding: u32,
+        usage: BufferUsage,
+    ) -> Result<Self, BufferError> {
+        if let Some(data) = data {
+            check_bounds(0, data.len(), capacity)?;
+        }
+
+        let _self = Self {
+            raw: RawBuffer::new(
+                gl::SHADER_STORAGE_BUFFER,
+                capacity_bytes::<T>(capacity)?,
+                data.map(bytemuck::cast_slice),
+                usage,
+            )?,
+            slot: binding,
+            _marker: PhantomData,
+        };
+        _self.bind_base();
+
+        Ok(_self)
+    }
+
+    /// Write the data starting at the element `offset`
+    pub fn send_data(&self, offset: usize, data: &[T]) -> Result<(), BufferError> {
+        check_bounds(offset, data.len(), self.capacity())?;
+        self.raw
+            .send_bytes(offset * size_of::<T>(), bytemuck::cast_slice(data))
+    }
+
+    /// Write the block with the `std430` layout starting at `offset` bytes, a array like
+    /// `[[f32; 3]; 100]` is written with the stride of a `vec3[]`
+    pub fn send_block<B: Std430>(&self, offset: usize, block: &B) -> Result<(), BufferError> {
+        self.raw.send_bytes(offset, &block.to_std430_bytes())
+    }
+
+    /// Read `count` elements starting at the element `offset`, it blocks until the gpu finish
+    /// writing the buffer
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        let mut data = vec![T::zeroed(); count];
+        self.raw
+            .read_bytes(offset * size_of::<T>(), bytemuck::cast_slice_mut(&mut data))?;
+        Ok(data)
+    }
+
+    /// Read all the elements of the buffer
+    pub fn read(&self) -> Result<Vec<T>, BufferError> {
+        self.read_range(0, self.capacity())
+    }
+
+    /// Map `count` elements starting at the element `offset`, see `RawBuffer::map_range`
+    pub fn map_range(
+        &self,
+        offset: usize,
+        count: usize,
+        flags: MapFlags,
+    ) -> Result<BufferMapping<'_, T>, BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        self.raw.map_range(offset * size_of::<T>(), count, flags)
+    }
+
+    /// Allocate space for `capacity` elements keeping the elements that fit, see
+    /// `RawBuffer::resize`. The buffer stays binded to its slot
+    pub fn resize(&self, capacity: usize) -> Result<(), BufferError> {
+        self.raw.resize(capacity_bytes::<T>(capacity)?)?;
+        self.bind_base();
+        Ok(())
+    }
+
+    /// Make sure that the buffer can hold at least `capacity` elements, the buffer grows at
+    /// least to the double of its capacity
+    pub fn reserve(&self, capacity: usize) -> Result<(), BufferError> {
+        if capacity > self.capacity() {
+            let grown = self.capacity().saturating_mul(2).min(max_capacity::<T>());
+            self.resize(capacity.max(grown))?;
+        }
+        Ok(())
+    }
+
+    /// The number of elements that the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.raw.size() / size_of::<T>().max(1)
+    }
+
+    /// Bind the whole buffer to its binding slot
+    pub fn bind_base(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.slot, self.raw.id());
+        }
+    }
+
+    /// Bind `count` elements starting at the element `offset` to the binding slot. The offset
+    /// in bytes has to be a multiple of `GL_SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT` and the count
+    /// can't be zero
+    pub fn bind_range(&self, offset: usize, count: usize) -> Result<(), BufferError> {
+        check_bounds(offset, count, self.capacity())?;
+        if count == 0 {
+            return Err(BufferError::EmptyRange);
+        }
+        unsafe {
+            gl::BindBufferRange(
+                gl::SHADER_STORAGE_BUFFER,
+                self.slot,
+                self.raw.id(),
+                (offset * size_of::<T>()) as isize,
+                (count * size_of::<T>()) as isize,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    pub fn bind(&self) {
+        self.raw.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.raw.unbind();
+    }
+}
+
+impl<T: Pod> GpuBuffer for StorageBuffer<T> {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
     }
 }
 
-impl Drop for IndexBuffer {
+impl Drop for VertexArray {
     fn drop(&mut self) {
-        unsafe { gl::DeleteBuffers(1, &self.id) }
+        unsafe { gl::DeleteVertexArrays(1, &self.id) }
     }
 }
 
-impl Drop for UniforBuffer {
+impl Drop for RawBuffer {
     fn drop(&mut self) {
         unsafe { gl::DeleteBuffers(1, &self.id) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Vertex)]
+    struct Particle {
+        position: [f32; 3],
+        #[vertex(normalize)]
+        color: [u8; 3],
+        #[vertex(skip)]
+        id: u64,
+        #[vertex(format = Half2, name = "aUv")]
+        uv: [u16; 2],
+        #[vertex(location = 5, divisor = 1)]
+        scale: f32,
+    }
+
+    #[test]
+    fn derive_vertex_uses_the_field_offsets_and_padding() {
+        let layout = Particle::layout();
+        assert_eq!(layout.stride, size_of::<Particle>() as u32);
+        assert_eq!(layout.stride, 32);
+
+        let offsets: Vec<u32> = layout.attribs.iter().map(|attrib| attrib.offset).collect();
+        assert_eq!(offsets, [0, 12, 24, 28]);
+    }
+
+    #[test]
+    fn derive_vertex_reads_the_field_attributes() {
+        let layout = Particle::layout();
+        let names: Vec<&str> = layout.attribs.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["position", "color", "aUv", "scale"]);
+
+        let [position, color, uv, scale] = &layout.attribs[..] else {
+            panic!("expected 4 attributes");
+        };
+        assert_eq!(position.vtype, VertexAttribType::Float3);
+        assert!(!position.normalize);
+        assert_eq!(position.location, None);
+
+        assert_eq!(color.vtype, VertexAttribType::Ubyte3);
+        assert!(color.normalize);
+
+        assert_eq!(uv.vtype, VertexAttribType::Half2);
+        assert_eq!(uv.size, 4);
+
+        assert_eq!(scale.vtype, VertexAttribType::Float);
+        assert_eq!(scale.location, Some(5));
+        assert_eq!(scale.divisor, 1);
+        assert_eq!(position.divisor, 0);
+    }
+
+    #[test]
+    fn capacity_bytes_rejects_overflowing_capacities() {
+        assert_eq!(capacity_bytes::<u32>(16), Ok(64));
+        assert_eq!(
+            capacity_bytes::<u32>(usize::MAX / 2),
+            Err(BufferError::OutOfBounds {
+                offset: 0,
+                len: usize::MAX / 2,
+                capacity: isize::MAX as usize / 4,
+            })
+        );
+    }
+
+    #[test]
+    fn explicit_formats_match_the_size_of_their_types() {
+        const {
+            assert!(
+                size_of::<[u16; 2]>() == vertex_attrib_type_size(&VertexAttribType::Half2) as usize
+            );
+            assert!(
+                size_of::<u32>()
+                    == vertex_attrib_type_size(&VertexAttribType::Int2101010Rev) as usize
+            );
+        };
+    }
+}
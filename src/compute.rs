@@ -0,0 +1,213 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::buffers::{BufferError, GpuBuffer};
+use crate::shader::{Shader, ShaderError, UniformType};
+
+/// The parameters of a indirect dispatch, the number of work groups on each axis. It has the
+/// layout that `glDispatchComputeIndirect` reads from the buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct DispatchIndirectCommand {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl DispatchIndirectCommand {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+/// The kinds of accesses that have to see the writes done by the shaders before the barrier,
+/// they can be combined with `|`
+///
+/// # Example
+///
+/// ``` Rust
+/// memory_barrier(Barrier::SHADER_STORAGE | Barrier::VERTEX_ATTRIB_ARRAY);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrier(pub u32);
+
+impl Barrier {
+    /// Vertex attributes read from buffers
+    pub const VERTEX_ATTRIB_ARRAY: Self = Self(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    /// Indices read from index buffers
+    pub const ELEMENT_ARRAY: Self = Self(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Self = Self(gl::UNIFORM_BARRIER_BIT);
+    /// Textures read by the shaders with samplers
+    pub const TEXTURE_FETCH: Self = Self(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: Self = Self(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    /// Parameters of the indirect draws and dispatches
+    pub const COMMAND: Self = Self(gl::COMMAND_BARRIER_BIT);
+    /// Transfers from and to a `PixelBuffer`
+    pub const PIXEL_BUFFER: Self = Self(gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: Self = Self(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    /// Copies, reads and mappings of buffers
+    pub const BUFFER_UPDATE: Self = Self(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: Self = Self(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const TRANSFORM_FEEDBACK: Self = Self(gl::TRANSFORM_FEEDBACK_BARRIER_BIT);
+    pub const ATOMIC_COUNTER: Self = Self(gl::ATOMIC_COUNTER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Self = Self(gl::SHADER_STORAGE_BARRIER_BIT);
+    /// Persistent mappings that aren't coherent
+    pub const CLIENT_MAPPED_BUFFER: Self = Self(gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT);
+    pub const QUERY_BUFFER: Self = Self(gl::QUERY_BUFFER_BARRIER_BIT);
+    pub const ALL: Self = Self(gl::ALL_BARRIER_BITS);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Barrier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Make the writes done by the shaders visible to the accesses on `barriers`, it has to be
+/// called between the dispatch that writes and the command that reads
+pub fn memory_barrier(barriers: Barrier) {
+    unsafe {
+        gl::MemoryBarrier(barriers.0);
+    }
+}
+
+/// A abstract representation of a compute shader program, it runs on groups of invocations
+/// instead of the vertices of a draw. The size of the work groups is declared on the source
+/// with `layout(local_size_x = ...) in;`
+///  # Example
+/// ``` Rust
+/// let mut culling = ComputeProgram::from_file("./shaders/culling.glsl")?;
+/// let [local_x, _, _] = culling.work_group_size();
+///
+/// culling.set_uniform("objects", UniformType::U32(objects));
+/// culling.dispatch(objects.div_ceil(local_x), 1, 1);
+/// memory_barrier(Barrier::COMMAND);
+///
+/// multi_draw_indirect(&vao, Topology::Triangles, &commands, 0..objects as usize)?;
+/// ```
+pub struct ComputeProgram {
+    shader: Shader,
+    work_group_size: [u32; 3],
+    max_work_group_count: [u32; 3],
+}
+
+impl ComputeProgram {
+    pub fn from_memory(compute_shader: &str) -> Result<Self, ShaderError> {
+        let mut shader = Shader::new();
+        shader.load_compute_from_memory(compute_shader)?;
+        Ok(Self::from_shader(shader))
+    }
+
+    pub fn from_file(compute_shader: &str) -> Result<Self, ShaderError> {
+        let mut shader = Shader::new();
+        shader.load_compute_from_file(compute_shader)?;
+        Ok(Self::from_shader(shader))
+    }
+
+    fn from_shader(shader: Shader) -> Self {
+        let mut size = [0i32; 3];
+        let mut max_count = [0i32; 3];
+        unsafe {
+            gl::GetProgramiv(
+                shader.program,
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                size.as_mut_ptr(),
+            );
+            for (axis, max) in max_count.iter_mut().enumerate() {
+                gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_COUNT, axis as u32, max);
+            }
+        }
+        Self {
+            shader,
+            work_group_size: size.map(|axis| axis as u32),
+            max_work_group_count: max_count.map(|axis| axis as u32),
+        }
+    }
+
+    /// The number of invocations of each work group on x, y and z, as declared on the source
+    pub fn work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    /// The max number of work groups of a dispatch on x, y and z, queried once when the
+    /// program is created
+    pub fn max_work_group_count(&self) -> [u32; 3] {
+        self.max_work_group_count
+    }
+
+    pub fn set_uniform(&mut self, name: &str, v: UniformType) {
+        self.shader.bind();
+        self.shader.set_uniform(name, v);
+    }
+
+    /// Run `x * y * z` work groups
+    ///
+    /// # Panics
+    /// If a axis has more groups than `GL_MAX_COMPUTE_WORK_GROUP_COUNT`
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        for (axis, (count, max)) in [x, y, z]
+            .into_iter()
+            .zip(self.max_work_group_count)
+            .enumerate()
+        {
+            assert!(
+                count <= max,
+                "{} work groups on the axis {}, the max is {}",
+                count,
+                axis,
+                max
+            );
+        }
+
+        self.bind();
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+        }
+    }
+
+    /// Run the work groups of the `DispatchIndirectCommand` at `offset` bytes of the buffer, the
+    /// parameters are read by the gpu so a previous dispatch can write them
+    pub fn dispatch_indirect<B: GpuBuffer + ?Sized>(
+        &self,
+        buffer: &B,
+        offset: usize,
+    ) -> Result<(), BufferError> {
+        let raw = buffer.raw();
+        let len = std::mem::size_of::<DispatchIndirectCommand>();
+        if offset.checked_add(len).is_none_or(|end| end > raw.size()) {
+            return Err(BufferError::OutOfBounds {
+                offset,
+                len,
+                capacity: raw.size(),
+            });
+        }
+        if !offset.is_multiple_of(4) {
+            return Err(BufferError::Misaligned { offset, align: 4 });
+        }
+
+        self.bind();
+        unsafe {
+            gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, raw.id());
+            gl::DispatchComputeIndirect(offset as isize);
+            gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    pub fn bind(&self) {
+        self.shader.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.shader.unbind();
+    }
+}
@@ -0,0 +1,698 @@
+use std::ffi::c_void;
+use std::fmt;
+use std::mem::size_of;
+use std::ops::Range;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::buffers::{DrawIndirectBuffer, GpuBuffer, IndirectCommand, VertexArray};
+
+/// The `GL_PARAMETER_BUFFER` target of gl 4.6, the buffer that holds the number of commands of
+/// a indirect count draw
+pub const PARAMETER_BUFFER: u32 = 0x80EE;
+
+/// How the vertices of a draw are assembled into primitives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    /// Lines with the neighbour vertices, only visible to a geometry shader
+    LinesAdjacency,
+    LineStripAdjacency,
+    /// Triangles with the neighbour vertices, only visible to a geometry shader
+    TrianglesAdjacency,
+    TriangleStripAdjacency,
+    /// Patches of N vertices for the tessellation shaders
+    Patches(u32),
+}
+
+impl Topology {
+    /// The gl enum used on the draw calls, like `gl::TRIANGLES`
+    pub fn gl_mode(&self) -> u32 {
+        match self {
+            Topology::Points => gl::POINTS,
+            Topology::Lines => gl::LINES,
+            Topology::LineStrip => gl::LINE_STRIP,
+            Topology::LineLoop => gl::LINE_LOOP,
+            Topology::Triangles => gl::TRIANGLES,
+            Topology::TriangleStrip => gl::TRIANGLE_STRIP,
+            Topology::TriangleFan => gl::TRIANGLE_FAN,
+            Topology::LinesAdjacency => gl::LINES_ADJACENCY,
+            Topology::LineStripAdjacency => gl::LINE_STRIP_ADJACENCY,
+            Topology::TrianglesAdjacency => gl::TRIANGLES_ADJACENCY,
+            Topology::TriangleStripAdjacency => gl::TRIANGLE_STRIP_ADJACENCY,
+            Topology::Patches(_) => gl::PATCHES,
+        }
+    }
+
+    /// Check the topology and set the gl state that it needs, the number of vertices of the
+    /// patches
+    pub(crate) fn prepare(&self) -> Result<(), DrawError> {
+        if let Topology::Patches(vertices) = *self {
+            let mut max = 0;
+            unsafe {
+                gl::GetIntegerv(gl::MAX_PATCH_VERTICES, &mut max);
+            }
+            self.check(max.max(0) as u32)?;
+            unsafe {
+                gl::PatchParameteri(gl::PATCH_VERTICES, vertices as i32);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the vertices of the patches against `GL_MAX_PATCH_VERTICES`
+    fn check(&self, max_patch_vertices: u32) -> Result<(), DrawError> {
+        match *self {
+            Topology::Patches(vertices) if vertices == 0 || vertices > max_patch_vertices => {
+                Err(DrawError::InvalidPatchVertices {
+                    vertices,
+                    max: max_patch_vertices,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Errors returned by the draw calls, they are checked before calling gl
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawError {
+    /// The draw uses indices but the vertex array doesn't have a index buffer
+    NoIndexBuffer,
+    /// The range `first..first + count` isn't inside the `available` indices of the index
+    /// buffer, or the commands of the indirect buffer
+    OutOfBounds {
+        first: usize,
+        count: usize,
+        available: usize,
+    },
+    /// A base vertex only makes sense on a draw with indices
+    BaseVertexWithoutIndices,
+    /// The vertices of a patch have to be between 1 and `GL_MAX_PATCH_VERTICES`
+    InvalidPatchVertices { vertices: u32, max: u32 },
+    /// The offset of the draw count has to be a multiple of 4 inside the parameter buffer
+    InvalidParameterOffset(usize),
+    /// The driver doesn't have the function, or it wasn't loaded
+    Unsupported(&'static str),
+    /// The value doesn't fit in the `GLsizei` or `GLint` that gl takes
+    TooLarge { name: &'static str, value: usize },
+}
+
+impl fmt::Display for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawError::NoIndexBuffer => write!(f, "the vertex array doesn't have a index buffer"),
+            DrawError::OutOfBounds {
+                first,
+                count,
+                available,
+            } => write!(
+                f,
+                "range {}..{} is out of the buffer (count {})",
+                first,
+                first.saturating_add(*count),
+                available
+            ),
+            DrawError::BaseVertexWithoutIndices => {
+                write!(f, "a base vertex needs a draw with indices")
+            }
+            DrawError::InvalidPatchVertices { vertices, max } => write!(
+                f,
+                "{} vertices per patch, it has to be between 1 and {}",
+                vertices, max
+            ),
+            DrawError::InvalidParameterOffset(offset) => {
+                write!(f, "invalid offset {} of the draw count", offset)
+            }
+            DrawError::Unsupported(function) => write!(f, "{} isn't supported", function),
+            DrawError::TooLarge { name, value } => {
+                write!(f, "the {} {} doesn't fit in a i32", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DrawError {}
+
+/// A draw call, it picks the gl draw function that matches the options. The draws with indices
+/// use the index buffer of the vertex array, its type and count are used to validate the call
+///  # Example
+/// ``` Rust
+/// // glDrawArrays
+/// DrawCall::arrays(Topology::Triangles, 0..3).submit(&vao)?;
+///
+/// // glDrawElementsInstancedBaseVertexBaseInstance
+/// DrawCall::elements(Topology::Triangles, 0..36)
+///     .with_instances(100)
+///     .with_base_vertex(24)
+///     .with_base_instance(100)
+///     .submit(&vao)?;
+///
+/// // glDrawRangeElements, the indices only reference the vertices 0..24
+/// DrawCall::elements(Topology::Triangles, 36..72)
+///     .with_vertex_range(0..24)
+///     .submit(&vao)?;
+///
+/// // The tessellation control shader receives patches of 4 vertices
+/// DrawCall::arrays(Topology::Patches(4), 0..64).submit(&vao)?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawCall {
+    topology: Topology,
+    first: usize,
+    count: usize,
+    indexed: bool,
+    instances: u32,
+    base_vertex: i32,
+    base_instance: u32,
+    vertex_range: Option<Range<u32>>,
+}
+
+impl DrawCall {
+    /// Draw the vertices on `vertices` in order
+    pub fn arrays(topology: Topology, vertices: Range<usize>) -> Self {
+        Self::new(topology, vertices, false)
+    }
+
+    /// Draw the vertices referenced by the indices on `indices`, the positions of the index
+    /// buffer of the vertex array
+    pub fn elements(topology: Topology, indices: Range<usize>) -> Self {
+        Self::new(topology, indices, true)
+    }
+
+    fn new(topology: Topology, range: Range<usize>, indexed: bool) -> Self {
+        Self {
+            topology,
+            first: range.start,
+            count: range.end.saturating_sub(range.start),
+            indexed,
+            instances: 1,
+            base_vertex: 0,
+            base_instance: 0,
+            vertex_range: None,
+        }
+    }
+
+    /// Draw `instances` copies, the shader can tell them apart with `gl_InstanceID` or with
+    /// attributes that have a divisor
+    pub fn with_instances(mut self, instances: u32) -> Self {
+        self.instances = instances;
+        self
+    }
+
+    /// Add `base_vertex` to each index before fetching the vertex, only for draws with indices
+    pub fn with_base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+
+    /// The first instance used to fetch the attributes with a divisor
+    pub fn with_base_instance(mut self, base_instance: u32) -> Self {
+        self.base_instance = base_instance;
+        self
+    }
+
+    /// Promise that the indices only reference the vertices on `vertices`, the driver can use
+    /// it to optimize the draw. It's ignored by the instanced draws because gl doesn't have a
+    /// instanced range draw
+    pub fn with_vertex_range(mut self, vertices: Range<u32>) -> Self {
+        self.vertex_range = Some(vertices);
+        self
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// The number of vertices or indices drawn by each instance
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Bind the vertex array and draw with the program currently binded
+    pub fn submit(&self, vao: &VertexArray) -> Result<(), DrawError> {
+        let indices = vao.index_buffer();
+        let params = self.validate(indices.map(|indices| indices.count()))?;
+        self.topology.prepare()?;
+        if params.count == 0 || params.instances == 0 {
+            return Ok(());
+        }
+
+        let mode = self.topology.gl_mode();
+        let instanced = self.instances != 1 || self.base_instance != 0;
+        let DrawParams {
+            first,
+            count,
+            instances,
+        } = params;
+
+        let Some(indices) = indices.filter(|_| self.indexed) else {
+            vao.bind();
+            unsafe {
+                if self.base_instance != 0 {
+                    gl::DrawArraysInstancedBaseInstance(
+                        mode,
+                        first,
+                        count,
+                        instances,
+                        self.base_instance,
+                    );
+                } else if instanced {
+                    gl::DrawArraysInstanced(mode, first, count, instances);
+                } else {
+                    gl::DrawArrays(mode, first, count);
+                }
+            }
+            return Ok(());
+        };
+
+        let index_type = indices.index_type() as u32;
+        let offset = (self.first * indices.index_type().size()) as *const c_void;
+
+        vao.bind();
+        unsafe {
+            if self.base_instance != 0 {
+                gl::DrawElementsInstancedBaseVertexBaseInstance(
+                    mode,
+                    count,
+                    index_type,
+                    offset,
+                    instances,
+                    self.base_vertex,
+                    self.base_instance,
+                );
+            } else if instanced && self.base_vertex != 0 {
+                gl::DrawElementsInstancedBaseVertex(
+                    mode,
+                    count,
+                    index_type,
+                    offset,
+                    instances,
+                    self.base_vertex,
+                );
+            } else if instanced {
+                gl::DrawElementsInstanced(mode, count, index_type, offset, instances);
+            } else if let Some(range) = &self.vertex_range {
+                // The range of gl is inclusive
+                let end = range.end.saturating_sub(1).max(range.start);
+                if self.base_vertex != 0 {
+                    gl::DrawRangeElementsBaseVertex(
+                        mode,
+                        range.start,
+                        end,
+                        count,
+                        index_type,
+                        offset,
+                        self.base_vertex,
+                    );
+                } else {
+                    gl::DrawRangeElements(mode, range.start, end, count, index_type, offset);
+                }
+            } else if self.base_vertex != 0 {
+                gl::DrawElementsBaseVertex(mode, count, index_type, offset, self.base_vertex);
+            } else {
+                gl::DrawElements(mode, count, index_type, offset);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the call and convert its values to the types of gl, `indices` is the count of the
+    /// index buffer of the vertex array if it has one
+    fn validate(&self, indices: Option<usize>) -> Result<DrawParams, DrawError> {
+        let first = if self.indexed {
+            let available = indices.ok_or(DrawError::NoIndexBuffer)?;
+            match self.first.checked_add(self.count) {
+                Some(end) if end <= available => {}
+                _ => {
+                    return Err(DrawError::OutOfBounds {
+                        first: self.first,
+                        count: self.count,
+                        available,
+                    })
+                }
+            }
+            // The first index is sent as a byte offset
+            0
+        } else if self.base_vertex != 0 {
+            return Err(DrawError::BaseVertexWithoutIndices);
+        } else {
+            to_i32("first vertex", self.first)?
+        };
+
+        Ok(DrawParams {
+            first,
+            count: to_i32("count", self.count)?,
+            instances: to_i32("instance count", self.instances as usize)?,
+        })
+    }
+}
+
+/// The values of a draw call with the types of gl, `first` is only used by the draws without
+/// indices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct DrawParams {
+    first: i32,
+    count: i32,
+    instances: i32,
+}
+
+fn to_i32(name: &'static str, value: usize) -> Result<i32, DrawError> {
+    i32::try_from(value).map_err(|_| DrawError::TooLarge { name, value })
+}
+
+type MultiDrawArraysIndirectCount = unsafe extern "system" fn(
+    mode: u32,
+    indirect: *const c_void,
+    drawcount: isize,
+    maxdrawcount: i32,
+    stride: i32,
+);
+
+type MultiDrawElementsIndirectCount = unsafe extern "system" fn(
+    mode: u32,
+    index_type: u32,
+    indirect: *const c_void,
+    drawcount: isize,
+    maxdrawcount: i32,
+    stride: i32,
+);
+
+static MULTI_DRAW_ARRAYS_INDIRECT_COUNT: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static MULTI_DRAW_ELEMENTS_INDIRECT_COUNT: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Load the indirect count functions of gl 4.6 or `GL_ARB_indirect_parameters`, they aren't
+/// part of the `gl` crate. Call it after `gl::load_with` with the same loader
+///  # Example
+/// ``` Rust
+/// gl::load_with(|name| video.gl_get_proc_address(name) as *const c_void);
+/// easy_opengl::draw::load_with(|name| video.gl_get_proc_address(name) as *const c_void);
+/// ```
+pub fn load_with<F: FnMut(&'static str) -> *const c_void>(mut loader: F) {
+    let mut load = |names: [&'static str; 2]| {
+        names
+            .into_iter()
+            .map(&mut loader)
+            .find(|ptr| !ptr.is_null())
+            .unwrap_or(std::ptr::null())
+    };
+
+    MULTI_DRAW_ARRAYS_INDIRECT_COUNT.store(
+        load([
+            "glMultiDrawArraysIndirectCount",
+            "glMultiDrawArraysIndirectCountARB",
+        ]) as *mut c_void,
+        Ordering::Relaxed,
+    );
+    MULTI_DRAW_ELEMENTS_INDIRECT_COUNT.store(
+        load([
+            "glMultiDrawElementsIndirectCount",
+            "glMultiDrawElementsIndirectCountARB",
+        ]) as *mut c_void,
+        Ordering::Relaxed,
+    );
+}
+
+/// Return true if the indirect count draws can be used, see `load_with`
+pub fn indirect_count_supported() -> bool {
+    !MULTI_DRAW_ARRAYS_INDIRECT_COUNT
+        .load(Ordering::Relaxed)
+        .is_null()
+        && !MULTI_DRAW_ELEMENTS_INDIRECT_COUNT
+            .load(Ordering::Relaxed)
+            .is_null()
+}
+
+/// Check the commands, bind the vertex array and the indirect buffer. Return the index type if
+/// the commands use indices
+fn prepare_indirect<T: IndirectCommand>(
+    vao: &VertexArray,
+    topology: Topology,
+    buffer: &DrawIndirectBuffer<T>,
+    commands: &Range<usize>,
+) -> Result<u32, DrawError> {
+    let count = commands.end.saturating_sub(commands.start);
+    if commands.end < commands.start || commands.end > buffer.count() {
+        return Err(DrawError::OutOfBounds {
+            first: commands.start,
+            count,
+            available: buffer.count(),
+        });
+    }
+
+    let index_type = if T::INDEXED {
+        vao.index_buffer()
+            .ok_or(DrawError::NoIndexBuffer)?
+            .index_type() as u32
+    } else {
+        0
+    };
+    topology.prepare()?;
+
+    vao.bind();
+    buffer.bind();
+    Ok(index_type)
+}
+
+/// Draw the command at the position `command` of the buffer, the parameters are read by the
+/// gpu so the buffer can be written by a compute shader
+///  # Example
+/// ``` Rust
+/// draw_indirect(&vao, Topology::Triangles, &commands, 0)?;
+/// ```
+pub fn draw_indirect<T: IndirectCommand>(
+    vao: &VertexArray,
+    topology: Topology,
+    buffer: &DrawIndirectBuffer<T>,
+    command: usize,
+) -> Result<(), DrawError> {
+    let end = command.checked_add(1).ok_or(DrawError::OutOfBounds {
+        first: command,
+        count: 1,
+        available: buffer.count(),
+    })?;
+    let index_type = prepare_indirect(vao, topology, buffer, &(command..end))?;
+    let offset = (command * size_of::<T>()) as *const c_void;
+
+    unsafe {
+        if T::INDEXED {
+            gl::DrawElementsIndirect(topology.gl_mode(), index_type, offset);
+        } else {
+            gl::DrawArraysIndirect(topology.gl_mode(), offset);
+        }
+    }
+    Ok(())
+}
+
+/// Draw the commands on `commands` with a single call
+pub fn multi_draw_indirect<T: IndirectCommand>(
+    vao: &VertexArray,
+    topology: Topology,
+    buffer: &DrawIndirectBuffer<T>,
+    commands: Range<usize>,
+) -> Result<(), DrawError> {
+    let index_type = prepare_indirect(vao, topology, buffer, &commands)?;
+    let offset = (commands.start * size_of::<T>()) as *const c_void;
+    let count = to_i32("command count", commands.end - commands.start)?;
+    if count == 0 {
+        return Ok(());
+    }
+
+    unsafe {
+        if T::INDEXED {
+            gl::MultiDrawElementsIndirect(
+                topology.gl_mode(),
+                index_type,
+                offset,
+                count,
+                size_of::<T>() as i32,
+            );
+        } else {
+            gl::MultiDrawArraysIndirect(topology.gl_mode(), offset, count, size_of::<T>() as i32);
+        }
+    }
+    Ok(())
+}
+
+/// Draw the commands on `commands` with a single call, but only the first N where N is a `u32`
+/// read by the gpu from `parameters` at `parameter_offset` bytes. Useful when a compute shader
+/// decides how many objects are visible. It needs `load_with`
+///  # Example
+/// ``` Rust
+/// // The culling shader writes the commands and the number of visible objects
+/// culling.dispatch(objects.div_ceil(64), 1, 1);
+/// memory_barrier(Barrier::COMMAND);
+/// multi_draw_indirect_count(&vao, Topology::Triangles, &commands, 0..objects, &visible, 0)?;
+/// ```
+pub fn multi_draw_indirect_count<T: IndirectCommand, P: GpuBuffer + ?Sized>(
+    vao: &VertexArray,
+    topology: Topology,
+    buffer: &DrawIndirectBuffer<T>,
+    commands: Range<usize>,
+    parameters: &P,
+    parameter_offset: usize,
+) -> Result<(), DrawError> {
+    let function = if T::INDEXED {
+        MULTI_DRAW_ELEMENTS_INDIRECT_COUNT.load(Ordering::Relaxed)
+    } else {
+        MULTI_DRAW_ARRAYS_INDIRECT_COUNT.load(Ordering::Relaxed)
+    };
+    if function.is_null() {
+        return Err(DrawError::Unsupported("glMultiDrawIndirectCount"));
+    }
+    if !parameter_offset.is_multiple_of(4)
+        || parameter_offset
+            .checked_add(4)
+            .is_none_or(|end| end > parameters.raw().size())
+    {
+        return Err(DrawError::InvalidParameterOffset(parameter_offset));
+    }
+
+    let index_type = prepare_indirect(vao, topology, buffer, &commands)?;
+    let offset = (commands.start * size_of::<T>()) as *const c_void;
+    let max_count = to_i32("command count", commands.end - commands.start)?;
+    if max_count == 0 {
+        return Ok(());
+    }
+
+    unsafe {
+        gl::BindBuffer(PARAMETER_BUFFER, parameters.raw().id());
+        if T::INDEXED {
+            let function: MultiDrawElementsIndirectCount = std::mem::transmute(function);
+            function(
+                topology.gl_mode(),
+                index_type,
+                offset,
+                parameter_offset as isize,
+                max_count,
+                size_of::<T>() as i32,
+            );
+        } else {
+            let function: MultiDrawArraysIndirectCount = std::mem::transmute(function);
+            function(
+                topology.gl_mode(),
+                offset,
+                parameter_offset as isize,
+                max_count,
+                size_of::<T>() as i32,
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrays_are_converted_to_gl_values() {
+        let call = DrawCall::arrays(Topology::Triangles, 3..9).with_instances(4);
+        assert_eq!(
+            call.validate(None),
+            Ok(DrawParams {
+                first: 3,
+                count: 6,
+                instances: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn arrays_that_dont_fit_in_i32_are_rejected() {
+        let first = i32::MAX as usize + 1;
+        let call = DrawCall::arrays(Topology::Points, first..first + 1);
+        assert_eq!(
+            call.validate(None),
+            Err(DrawError::TooLarge {
+                name: "first vertex",
+                value: first,
+            })
+        );
+
+        let call = DrawCall::arrays(Topology::Points, 0..first);
+        assert_eq!(
+            call.validate(None),
+            Err(DrawError::TooLarge {
+                name: "count",
+                value: first,
+            })
+        );
+
+        let call = DrawCall::arrays(Topology::Points, 0..1).with_instances(u32::MAX);
+        assert_eq!(
+            call.validate(None),
+            Err(DrawError::TooLarge {
+                name: "instance count",
+                value: u32::MAX as usize,
+            })
+        );
+    }
+
+    #[test]
+    fn base_vertex_needs_indices() {
+        let call = DrawCall::arrays(Topology::Triangles, 0..3).with_base_vertex(1);
+        assert_eq!(
+            call.validate(None),
+            Err(DrawError::BaseVertexWithoutIndices)
+        );
+    }
+
+    #[test]
+    fn elements_are_checked_against_the_index_buffer() {
+        let call = DrawCall::elements(Topology::Triangles, 30..36);
+        assert_eq!(call.validate(None), Err(DrawError::NoIndexBuffer));
+        assert_eq!(call.validate(Some(36)).map(|params| params.count), Ok(6));
+        assert_eq!(
+            call.validate(Some(35)),
+            Err(DrawError::OutOfBounds {
+                first: 30,
+                count: 6,
+                available: 35,
+            })
+        );
+    }
+
+    #[test]
+    fn elements_range_overflow_is_out_of_bounds() {
+        let mut call = DrawCall::elements(Topology::Triangles, 0..6);
+        call.first = usize::MAX;
+        let err = call.validate(Some(36)).unwrap_err();
+        assert_eq!(
+            err,
+            DrawError::OutOfBounds {
+                first: usize::MAX,
+                count: 6,
+                available: 36,
+            }
+        );
+        // The message doesn't overflow either
+        assert!(err.to_string().contains("out of the buffer"));
+    }
+
+    #[test]
+    fn patch_vertices_are_checked_against_the_limit() {
+        assert_eq!(Topology::Patches(3).check(32), Ok(()));
+        assert_eq!(Topology::Patches(32).check(32), Ok(()));
+        assert_eq!(
+            Topology::Patches(0).check(32),
+            Err(DrawError::InvalidPatchVertices {
+                vertices: 0,
+                max: 32,
+            })
+        );
+        assert_eq!(
+            Topology::Patches(33).check(32),
+            Err(DrawError::InvalidPatchVertices {
+                vertices: 33,
+                max: 32,
+            })
+        );
+        assert_eq!(Topology::Triangles.check(0), Ok(()));
+    }
+}
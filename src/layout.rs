@@ -0,0 +1,339 @@
+//! Layouts of the GLSL interface blocks. The `std140` layout is used by the uniform blocks and
+//! the `std430` by the shader storage blocks, derive `Std140` or `Std430` on a struct to write it
+//! with the padding expected by the shader.
+//!
+//! The rust types map to GLSL like this:
+//! * `f32`, `i32`, `u32`, `bool`, `f64` - `float`, `int`, `uint`, `bool`, `double`
+//! * `[f32; 2]`, `[f32; 3]`, `[f32; 4]` - `vec2`, `vec3`, `vec4`, the same for `ivec`, `uvec`
+//!   and `dvec`
+//! * `[[f32; 4]; 4]` - `mat4`, a matrix is a array of columns, the same for `mat2` and `mat3`
+//! * `[T; N]` - `T[N]`, because `[f32; N]` is a vector the arrays of scalars are written as
+//!   `[[f32; 1]; N]`
+//!
+//! # Example
+//! ``` Rust
+//! // layout(std140, binding = 0) uniform Light {
+//! //     vec3 position;
+//! //     float intensity;
+//! //     mat3 rotation;
+//! //     vec4 colors[2];
+//! // };
+//! #[derive(Std140)]
+//! #[std140(size = 96)] // Checked at compile time
+//! struct Light {
+//!     position: [f32; 3],
+//!     intensity: f32,
+//!     rotation: [[f32; 3]; 3],
+//!     colors: [[f32; 4]; 2],
+//! }
+//!
+//! let ubo = UniforBuffer::for_block::<Light>(0, BufferUsage::DynamicDraw);
+//! ubo.send_block(0, &light)?;
+//! ```
+
+pub use easy_opengl_derive::{Std140, Std430};
+
+/// A type with a known `std140` layout
+pub trait Std140 {
+    /// The base alignment in bytes
+    const STD140_ALIGN: usize;
+    /// The size in bytes, including the padding at the end
+    const STD140_SIZE: usize;
+
+    /// Write the value on `out`, it has `STD140_SIZE` bytes
+    fn write_std140(&self, out: &mut [u8]);
+
+    /// Return the value written with the `std140` layout
+    fn to_std140_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::STD140_SIZE];
+        self.write_std140(&mut bytes);
+        bytes
+    }
+}
+
+/// A type with a known `std430` layout
+pub trait Std430 {
+    /// The base alignment in bytes
+    const STD430_ALIGN: usize;
+    /// The size in bytes, including the padding at the end
+    const STD430_SIZE: usize;
+
+    /// Write the value on `out`, it has `STD430_SIZE` bytes
+    fn write_std430(&self, out: &mut [u8]);
+
+    /// Return the value written with the `std430` layout
+    fn to_std430_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::STD430_SIZE];
+        self.write_std430(&mut bytes);
+        bytes
+    }
+}
+
+/// A type that can be the element of a array in a `std140` block, every type except the bare
+/// scalars
+pub trait Std140ArrayElement {}
+
+/// A type that can be the element of a array in a `std430` block, every type except the bare
+/// scalars
+pub trait Std430ArrayElement {}
+
+/// Round `offset` up to a multiple of `align`
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// The max of two sizes, `std::cmp::max` isn't const
+pub const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+trait Scalar: Copy {
+    const SIZE: usize;
+
+    fn write_scalar(&self, out: &mut [u8]);
+}
+
+macro_rules! impl_scalar {
+    ($($scalar:ty),*) => {
+        $(
+            impl Scalar for $scalar {
+                const SIZE: usize = std::mem::size_of::<$scalar>();
+
+                fn write_scalar(&self, out: &mut [u8]) {
+                    out[..Self::SIZE].copy_from_slice(&self.to_ne_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar!(f32, i32, u32, f64);
+
+impl Scalar for bool {
+    const SIZE: usize = 4;
+
+    fn write_scalar(&self, out: &mut [u8]) {
+        (*self as u32).write_scalar(out);
+    }
+}
+
+fn write_components<S: Scalar>(components: &[S], out: &mut [u8]) {
+    for (i, component) in components.iter().enumerate() {
+        component.write_scalar(&mut out[i * S::SIZE..]);
+    }
+}
+
+macro_rules! impl_block_type {
+    ($ty:ty, $align:expr, $size:expr, |$value:ident| $components:expr) => {
+        impl Std140 for $ty {
+            const STD140_ALIGN: usize = $align;
+            const STD140_SIZE: usize = $size;
+
+            fn write_std140(&self, out: &mut [u8]) {
+                let $value = self;
+                write_components($components, out);
+            }
+        }
+
+        impl Std430 for $ty {
+            const STD430_ALIGN: usize = $align;
+            const STD430_SIZE: usize = $size;
+
+            fn write_std430(&self, out: &mut [u8]) {
+                let $value = self;
+                write_components($components, out);
+            }
+        }
+    };
+}
+
+macro_rules! impl_block_vectors {
+    ($($scalar:ty),*) => {
+        $(
+            impl_block_type!($scalar, <$scalar as Scalar>::SIZE, <$scalar as Scalar>::SIZE, |v| std::slice::from_ref(v));
+            impl_block_type!([$scalar; 1], <$scalar as Scalar>::SIZE, <$scalar as Scalar>::SIZE, |v| v);
+            impl_block_type!([$scalar; 2], 2 * <$scalar as Scalar>::SIZE, 2 * <$scalar as Scalar>::SIZE, |v| v);
+            impl_block_type!([$scalar; 3], 4 * <$scalar as Scalar>::SIZE, 3 * <$scalar as Scalar>::SIZE, |v| v);
+            impl_block_type!([$scalar; 4], 4 * <$scalar as Scalar>::SIZE, 4 * <$scalar as Scalar>::SIZE, |v| v);
+
+            impl Std140ArrayElement for [$scalar; 1] {}
+            impl Std140ArrayElement for [$scalar; 2] {}
+            impl Std140ArrayElement for [$scalar; 3] {}
+            impl Std140ArrayElement for [$scalar; 4] {}
+            impl Std430ArrayElement for [$scalar; 1] {}
+            impl Std430ArrayElement for [$scalar; 2] {}
+            impl Std430ArrayElement for [$scalar; 3] {}
+            impl Std430ArrayElement for [$scalar; 4] {}
+        )*
+    };
+}
+
+impl_block_vectors!(f32, i32, u32, bool, f64);
+
+/// The arrays, and the matrices as arrays of columns. On `std140` the stride of the elements is
+/// rounded up to the alignment of a `vec4`
+impl<T: Std140 + Std140ArrayElement, const N: usize> Std140 for [T; N] {
+    const STD140_ALIGN: usize = max(T::STD140_ALIGN, 16);
+    const STD140_SIZE: usize = N * align_up(T::STD140_SIZE, Self::STD140_ALIGN);
+
+    fn write_std140(&self, out: &mut [u8]) {
+        let stride = align_up(T::STD140_SIZE, Self::STD140_ALIGN);
+        for (i, element) in self.iter().enumerate() {
+            element.write_std140(&mut out[i * stride..i * stride + T::STD140_SIZE]);
+        }
+    }
+}
+
+impl<T: Std140ArrayElement, const N: usize> Std140ArrayElement for [T; N] {}
+
+impl<T: Std430 + Std430ArrayElement, const N: usize> Std430 for [T; N] {
+    const STD430_ALIGN: usize = T::STD430_ALIGN;
+    const STD430_SIZE: usize = N * align_up(T::STD430_SIZE, Self::STD430_ALIGN);
+
+    fn write_std430(&self, out: &mut [u8]) {
+        let stride = align_up(T::STD430_SIZE, Self::STD430_ALIGN);
+        for (i, element) in self.iter().enumerate() {
+            element.write_std430(&mut out[i * stride..i * stride + T::STD430_SIZE]);
+        }
+    }
+}
+
+impl<T: Std430ArrayElement, const N: usize> Std430ArrayElement for [T; N] {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[derive(Std140, Std430)]
+    struct Light {
+        position: [f32; 3],
+        intensity: f32,
+    }
+
+    #[derive(Std140, Std430)]
+    struct Material {
+        light: Light,
+        shininess: f32,
+    }
+
+    #[derive(Std140, Std430)]
+    struct Scene {
+        ambient: f32,
+        lights: [Light; 2],
+    }
+
+    #[derive(Std140, Std430)]
+    struct Weights {
+        weights: [[f32; 1]; 3],
+        bias: f32,
+    }
+
+    #[derive(Std140, Std430)]
+    struct Pair {
+        a: f32,
+        b: [f32; 2],
+    }
+
+    #[test]
+    fn vec3_followed_by_float_shares_the_slot() {
+        let light = Light {
+            position: [1.0, 2.0, 3.0],
+            intensity: 4.0,
+        };
+        assert_eq!(Light::STD140_SIZE, 16);
+        assert_eq!(Light::STD430_SIZE, 16);
+
+        let bytes = light.to_std140_bytes();
+        assert_eq!(f32_at(&bytes, 8), 3.0);
+        assert_eq!(f32_at(&bytes, 12), 4.0);
+        assert_eq!(light.to_std430_bytes(), bytes);
+    }
+
+    #[test]
+    fn mat3_columns_have_a_vec4_stride() {
+        assert_eq!(<[[f32; 3]; 3]>::STD140_ALIGN, 16);
+        assert_eq!(<[[f32; 3]; 3]>::STD140_SIZE, 48);
+        assert_eq!(<[[f32; 3]; 3]>::STD430_SIZE, 48);
+
+        let rotation: [[f32; 3]; 3] = [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]];
+        let bytes = rotation.to_std140_bytes();
+        assert_eq!(f32_at(&bytes, 16), 4.0);
+        assert_eq!(f32_at(&bytes, 40), 9.0);
+        assert_eq!(f32_at(&bytes, 44), 0.0);
+    }
+
+    #[test]
+    fn scalar_arrays_are_padded_only_on_std140() {
+        assert_eq!(<[[f32; 1]; 3]>::STD140_SIZE, 48);
+        assert_eq!(<[[f32; 1]; 3]>::STD430_SIZE, 12);
+        assert_eq!(Weights::STD140_SIZE, 64);
+        assert_eq!(Weights::STD430_SIZE, 16);
+
+        let weights = Weights {
+            weights: [[1.0], [2.0], [3.0]],
+            bias: 4.0,
+        };
+        let std140 = weights.to_std140_bytes();
+        assert_eq!(f32_at(&std140, 16), 2.0);
+        assert_eq!(f32_at(&std140, 48), 4.0);
+        let std430 = weights.to_std430_bytes();
+        assert_eq!(f32_at(&std430, 4), 2.0);
+        assert_eq!(f32_at(&std430, 12), 4.0);
+    }
+
+    #[test]
+    fn nested_struct_is_aligned_to_a_vec4_on_std140() {
+        assert_eq!(Material::STD140_ALIGN, 16);
+        assert_eq!(Material::STD140_SIZE, 32);
+        assert_eq!(Material::STD430_SIZE, 32);
+
+        let material = Material {
+            light: Light {
+                position: [0.0; 3],
+                intensity: 1.0,
+            },
+            shininess: 2.0,
+        };
+        assert_eq!(f32_at(&material.to_std140_bytes(), 16), 2.0);
+    }
+
+    #[test]
+    fn struct_arrays_use_the_struct_size_as_stride() {
+        assert_eq!(Scene::STD140_SIZE, 48);
+        assert_eq!(Scene::STD430_SIZE, 48);
+
+        let light = |i: f32| Light {
+            position: [i; 3],
+            intensity: i,
+        };
+        let scene = Scene {
+            ambient: 0.5,
+            lights: [light(1.0), light(2.0)],
+        };
+        let bytes = scene.to_std140_bytes();
+        assert_eq!(f32_at(&bytes, 0), 0.5);
+        assert_eq!(f32_at(&bytes, 16), 1.0);
+        assert_eq!(f32_at(&bytes, 32), 2.0);
+        assert_eq!(f32_at(&bytes, 44), 2.0);
+    }
+
+    #[test]
+    fn std430_struct_isnt_rounded_to_a_vec4() {
+        assert_eq!(Pair::STD140_ALIGN, 16);
+        assert_eq!(Pair::STD140_SIZE, 16);
+        assert_eq!(Pair::STD430_ALIGN, 8);
+        assert_eq!(Pair::STD430_SIZE, 16);
+        assert_eq!(<[[f32; 2]; 2]>::STD140_SIZE, 32);
+        assert_eq!(<[[f32; 2]; 2]>::STD430_SIZE, 16);
+        assert_eq!(<[Pair; 2]>::STD430_SIZE, 32);
+        assert_eq!(<[[f32; 3]; 2]>::STD430_SIZE, 32);
+    }
+}
@@ -3,7 +3,7 @@
 //! `easy-opengl` is a collection of utilities to make opengl app
 //! more quickly and easy without losing custumization and freedom
 //! # Example
-//! ``` rust
+//! ``` rust,ignore
 //!extern crate gl;
 //!extern crate sdl2;
 //!
@@ -84,22 +84,24 @@
 //!pub fn main() {
 //!    let mut window = Window::new(SCR_WIDTH, SCR_HEIGHT, "Test".to_string());
 //!    let mut shader = Shader::new();
-//!    shader.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None);
+//!    if let Err(err) = shader.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None) {
+//!        panic!("{}", err);
+//!    }
 //!
-//!    let vertices = vec![
+//!    let vertices: Vec<f32> = vec![
 //!        0.5, 0.5, 0.0, 1.0, 1.0, // top right
 //!        0.5, -0.5, 0.0, 1.0, 0.0, // bottom right
 //!        -0.5, -0.5, 0.0, 0.0, 0.0, // bottom left
 //!        -0.5, 0.5, 0.0, 0.0, 1.0, // top left
 //!    ];
-//!    let indices = vec![
+//!    let indices: Vec<u32> = vec![
 //!        0, 1, 3, // first Triangle
 //!        1, 2, 3, // second Triangle
 //!    ];
 //!
 //!    let vao = VertexArray::new();
 //!    // Is important keep alive the variable, because when is out of scope it will destroy the buffer
-//!    let _vbo = VertexBuffer::new(calc_bytes_size(&vertices) as isize, Some(&vertices));
+//!    let _vbo = VertexBuffer::from_slice(&vertices);
 //!
 //!    vao.bind();
 //!
@@ -109,7 +111,7 @@
 //!    ]);
 //!
 //!    // Is important keep alive the variable, because when is out of scope it will destroy the buffer
-//!    let _ibo = IndexBuffer::new(calc_bytes_size(&indices) as isize, Some(&indices));
+//!    let ibo = IndexBuffer::from_slice(&indices);
 //!
 //!    let mut texture = Texture2D::new();
 //!    texture.load_from_file("./a.png", TextureConfig::new());
@@ -126,8 +128,8 @@
 //!                vao.bind();
 //!                gl::DrawElements(
 //!                    gl::TRIANGLES,
-//!                    indices.len() as i32,
-//!                    gl::UNSIGNED_INT,
+//!                    ibo.count() as i32,
+//!                    ibo.index_type() as u32,
 //!                    std::ptr::null(),
 //!                );
 //!            }
@@ -153,7 +155,22 @@
 //!}
 //! ```
 
+pub use bytemuck;
+
+// The derives refer to `::easy_opengl`, so the tests of the crate can use them
+#[cfg(test)]
+extern crate self as easy_opengl;
+
 #[allow(dead_code)]
 pub mod buffers;
+pub mod compute;
+pub mod draw;
+pub mod layout;
+pub mod mesh;
+pub mod preprocessor;
+pub mod query;
+pub mod ring_buffer;
 pub mod shader;
+pub mod sync;
 pub mod textures;
+pub mod transform_feedback;
@@ -0,0 +1,151 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use bytemuck::Pod;
+
+use crate::buffers::{
+    GpuBuffer, IndexBuffer, IndexElement, Vertex, VertexArray, VertexBuffer, VertexLayout,
+};
+use crate::draw::{DrawCall, DrawError, Topology};
+
+/// A abstract representation of a mesh, it owns the vertex array with its buffers and knows
+/// how to draw them. If the mesh has a index buffer the draws use its indices, else they use
+/// the vertices in order
+///  # Example
+/// ``` Rust
+/// let quad = Mesh::indexed(Topology::Triangles, &vertices, &[0u16, 1, 3, 1, 2, 3]);
+///
+/// shader.bind();
+/// quad.draw();
+///
+/// // Only the second triangle
+/// quad.draw_range(3..6)?;
+///
+/// // 100 quads, the per instance data comes from a buffer with divisor 1
+/// let mut particles = Mesh::with_vertices(Topology::TriangleStrip, &corners);
+/// particles.add_vertex_buffer(instance_buffer, InstanceData::layout());
+/// particles.draw_instanced(100);
+/// ```
+pub struct Mesh {
+    vao: VertexArray,
+    topology: Topology,
+    vertex_count: usize,
+}
+
+impl Mesh {
+    /// Return a mesh without buffers, add them with `add_vertex_buffer` and `set_index_buffer`
+    pub fn new(topology: Topology) -> Self {
+        Self {
+            vao: VertexArray::new(),
+            topology,
+            vertex_count: 0,
+        }
+    }
+
+    /// Return a mesh that draws all the vertices in order, the vertices are uploaded to a
+    /// static vertex buffer
+    pub fn with_vertices<V: Vertex + Pod>(topology: Topology, vertices: &[V]) -> Self {
+        let mut _self = Self::new(topology);
+        _self.add_vertex_buffer(Rc::new(VertexBuffer::from_slice(vertices)), V::layout());
+        _self.vertex_count = vertices.len();
+        _self
+    }
+
+    /// Return a mesh that draws the vertices using the indices, both are uploaded to static
+    /// buffers
+    pub fn indexed<V: Vertex + Pod, I: IndexElement>(
+        topology: Topology,
+        vertices: &[V],
+        indices: &[I],
+    ) -> Self {
+        let mut _self = Self::with_vertices(topology, vertices);
+        _self.set_index_buffer(Rc::new(IndexBuffer::from_slice(indices)));
+        _self
+    }
+
+    /// Attach a vertex buffer to the vertex array of the mesh, see
+    /// `VertexArray::add_vertex_buffer`. It doesn't change the number of vertices drawn
+    pub fn add_vertex_buffer<B: GpuBuffer + 'static>(
+        &mut self,
+        buffer: Rc<B>,
+        layout: VertexLayout,
+    ) {
+        self.vao.add_vertex_buffer(buffer, layout);
+    }
+
+    /// Attach a index buffer, the draws use its indices from now on
+    pub fn set_index_buffer<T: IndexElement>(&mut self, buffer: Rc<IndexBuffer<T>>) {
+        self.vao.set_index_buffer(buffer);
+    }
+
+    /// Set the number of vertices drawn when the mesh doesn't have a index buffer
+    pub fn set_vertex_count(&mut self, count: usize) {
+        self.vertex_count = count;
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// The number of elements drawn by `draw`, the count of the index buffer if the mesh has
+    /// one, else the number of vertices
+    pub fn count(&self) -> usize {
+        match self.vao.index_buffer() {
+            Some(indices) => indices.count(),
+            None => self.vertex_count,
+        }
+    }
+
+    pub fn vertex_array(&self) -> &VertexArray {
+        &self.vao
+    }
+
+    /// Return the draw call of the elements on `range`, indices if the mesh has a index buffer,
+    /// else vertices. Use it to add options like a base instance
+    pub fn draw_call(&self, range: Range<usize>) -> DrawCall {
+        match self.vao.index_buffer() {
+            Some(_) => DrawCall::elements(self.topology, range),
+            None => DrawCall::arrays(self.topology, range),
+        }
+    }
+
+    /// Draw all the elements of the mesh with the program currently binded
+    ///
+    /// # Panics
+    /// If the topology is a invalid `Topology::Patches`
+    pub fn draw(&self) {
+        self.draw_instanced(1);
+    }
+
+    /// Draw `instances` copies of the mesh, the shader can tell them apart with
+    /// `gl_InstanceID` or with attributes that have a divisor
+    ///
+    /// # Panics
+    /// If the topology is a invalid `Topology::Patches`
+    pub fn draw_instanced(&self, instances: u32) {
+        if let Err(err) = self
+            .draw_call(0..self.count())
+            .with_instances(instances)
+            .submit(&self.vao)
+        {
+            panic!("Couldn't draw the mesh: {}", err);
+        }
+    }
+
+    /// Draw only the elements on `range`, indices if the mesh has a index buffer, else vertices
+    pub fn draw_range(&self, range: Range<usize>) -> Result<(), DrawError> {
+        if range.end < range.start || range.end > self.count() {
+            return Err(DrawError::OutOfBounds {
+                first: range.start,
+                count: range.end.saturating_sub(range.start),
+                available: self.count(),
+            });
+        }
+
+        self.draw_call(range).submit(&self.vao)
+    }
+}
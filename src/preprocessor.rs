@@ -0,0 +1,710 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::shader::{read_source, ShaderDiagnostic, ShaderError};
+
+/// A set of `#define`s injected on a source after its `#version`, each name has a value that
+/// can be empty. The same defines in any order are equal, so they can be used as the key of a
+/// cache of variants. The names have to be identifiers and the values a single line
+///  # Example
+/// ``` Rust
+/// // #define SKINNING
+/// // #define MAX_LIGHTS 8
+/// let defines = ShaderDefines::new()
+///     .with_flag("SKINNING")?
+///     .with_value("MAX_LIGHTS", "8")?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define `name` without value, like `#define NORMAL_MAP`
+    pub fn with_flag(self, name: &str) -> Result<Self, ShaderError> {
+        self.with_value(name, "")
+    }
+
+    /// Define `name` with `value`, like `#define MAX_LIGHTS 8`. It replaces the previous value
+    pub fn with_value(mut self, name: &str, value: &str) -> Result<Self, ShaderError> {
+        self.insert(name, value)?;
+        Ok(self)
+    }
+
+    /// Define `name` with `value`, it fails if `name` isn't a identifier or `value` would add
+    /// lines to the source
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<(), ShaderError> {
+        validate_define(name, value)?;
+        self.defines.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    /// Add the defines of `other`, its values replace the values of the same names
+    pub fn extend(&mut self, other: &ShaderDefines) {
+        self.defines.extend(other.defines.clone());
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defines
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.defines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+}
+
+fn validate_define(name: &str, value: &str) -> Result<(), ShaderError> {
+    let invalid = |reason| ShaderError::InvalidDefine {
+        name: name.to_string(),
+        reason,
+    };
+    let mut chars = name.chars();
+    if !chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(invalid("the name isn't a identifier"));
+    }
+    // A line break or a trailing `\` would move the lines after the defines
+    if value.contains(['\n', '\r']) || value.trim_end().ends_with('\\') {
+        return Err(invalid("the value has to be a single line"));
+    }
+    Ok(())
+}
+
+/// A file of a preprocessed source, its index is the source string number of the `#line`
+/// directives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+/// A source with the includes resolved, it knows the files that it's made of to map the lines
+/// of the driver back to them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessedSource {
+    pub source: String,
+    /// The files in the order they were included, the first one is the root file
+    pub files: Vec<SourceFile>,
+}
+
+impl PreprocessedSource {
+    /// Insert the defines after the `#version` of the root file, or at the start if it doesn't
+    /// have one. A `#line` directive after them keeps the lines of the root file
+    pub fn insert_defines(&mut self, defines: &ShaderDefines) {
+        if defines.is_empty() {
+            return;
+        }
+
+        let lines: Vec<&str> = self.source.split_inclusive('\n').collect();
+        let version = lines.iter().position(|line| is_version(line));
+        let (head, tail) = lines.split_at(version.map_or(0, |version| version + 1));
+
+        let mut source = head.concat();
+        if !source.is_empty() && !source.ends_with('\n') {
+            source.push('\n');
+        }
+        for (name, value) in defines.iter() {
+            let define = format!("#define {} {}", name, value);
+            source.push_str(define.trim_end());
+            source.push('\n');
+        }
+        source.push_str(&format!("#line {} 0\n", head.len() + 1));
+        source.push_str(&tail.concat());
+        self.source = source;
+    }
+
+    /// Set the file and the line of code of the diagnostics, the source string number reported
+    /// by the driver is the index of the file
+    pub fn map_diagnostics(&self, diagnostics: &mut [ShaderDiagnostic]) {
+        for diagnostic in diagnostics {
+            let (Some(file), Some(line)) =
+                (self.files.get(diagnostic.source as usize), diagnostic.line)
+            else {
+                continue;
+            };
+            diagnostic.file = Some(file.name.clone());
+            diagnostic.code = (line as usize)
+                .checked_sub(1)
+                .and_then(|line| file.text.lines().nth(line))
+                .map(|code| code.to_string());
+        }
+    }
+}
+
+/// A preprocessor that resolves the `#include "file"` and `#include <file>` directives of the
+/// shaders. The included files are searched on this order:
+/// * The virtual files added with `add_virtual_file`, relative to the file that includes them
+///   and then by name
+/// * Relative to the file that includes them, only for `"file"`
+/// * The search roots added with `add_root`
+///
+/// Each included file is surrounded by `#line` directives, so the lines reported by the driver
+/// can be mapped back with `PreprocessedSource::map_diagnostics`. The `#line` directives follow
+/// the rules of GLSL 3.30 and later. The `#version` of the included files is commented.
+///
+/// A file with `#pragma once` is only pasted the first time it's included, the other files need
+/// include guards to be included twice, like when two included files include the same one. The
+/// includes inside `/* */` comments and `#if 0` blocks are ignored, the other conditionals are
+/// left to the driver so their includes are always resolved
+///  # Example
+/// ``` Rust
+/// let mut preprocessor = Preprocessor::new();
+/// preprocessor.add_root("./shaders/common");
+/// preprocessor.add_virtual_file("generated/lights.glsl", &lights_source);
+///
+/// // #include "lighting.glsl" is found on ./shaders/common
+/// let shader = ShaderBuilder::new()
+///     .with_preprocessor(preprocessor)
+///     .with_file(ShaderStage::Vertex, "./shaders/mesh.vert")
+///     .with_file(ShaderStage::Fragment, "./shaders/mesh.frag")
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    roots: Vec<PathBuf>,
+    virtual_files: HashMap<String, String>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directory where the included files are searched
+    pub fn add_root<P: AsRef<Path>>(&mut self, root: P) {
+        self.roots.push(root.as_ref().to_path_buf());
+    }
+
+    /// Add a file that only exists on memory, it can be included with `name`. It has priority
+    /// over the files of the search roots
+    pub fn add_virtual_file(&mut self, name: &str, source: &str) {
+        self.virtual_files
+            .insert(normalize(Path::new(name)), source.to_string());
+    }
+
+    /// Resolve the includes of the file `filepath`
+    pub fn process_file(&self, filepath: &str) -> Result<PreprocessedSource, ShaderError> {
+        let name = normalize(Path::new(filepath));
+        let text = match self.virtual_files.get(&name) {
+            Some(text) => text.clone(),
+            None => read_source(filepath)?,
+        };
+        self.process(&name, &text)
+    }
+
+    /// Resolve the includes of `source`, `name` is used to find the relative includes and to
+    /// report the errors
+    pub fn process(&self, name: &str, source: &str) -> Result<PreprocessedSource, ShaderError> {
+        let mut processed = PreprocessedSource {
+            source: String::new(),
+            files: vec![SourceFile {
+                name: name.to_string(),
+                text: source.to_string(),
+            }],
+        };
+        let mut stack = vec![name.to_string()];
+        self.expand(0, &mut processed, &mut stack, &mut HashSet::new())?;
+        Ok(processed)
+    }
+
+    fn expand(
+        &self,
+        file: usize,
+        processed: &mut PreprocessedSource,
+        stack: &mut Vec<String>,
+        once: &mut HashSet<String>,
+    ) -> Result<(), ShaderError> {
+        let name = processed.files[file].name.clone();
+        let text = processed.files[file].text.clone();
+
+        let mut in_comment = false;
+        // The depth of the conditionals inside a `#if 0`, 0 when the lines are compiled
+        let mut disabled = 0usize;
+        for (i, line) in text.lines().enumerate() {
+            let active = !in_comment && disabled == 0;
+            if !in_comment {
+                disabled = update_disabled(line, disabled);
+            }
+            in_comment = ends_in_comment(line, in_comment);
+
+            let include = if active { parse_include(line) } else { None };
+            let Some((include, relative)) = include else {
+                let pragma_once = active && is_pragma_once(line);
+                if pragma_once {
+                    once.insert(name.clone());
+                }
+                if (file != 0 && is_version(line)) || pragma_once {
+                    processed.source.push_str("// ");
+                }
+                processed.source.push_str(line);
+                processed.source.push('\n');
+                continue;
+            };
+
+            let (include_name, include_text) =
+                self.resolve(include, relative, &name)?.ok_or_else(|| {
+                    ShaderError::IncludeNotFound {
+                        file: name.clone(),
+                        line: i as u32 + 1,
+                        include: include.to_string(),
+                    }
+                })?;
+            if once.contains(&include_name) {
+                // Keep the line so the next lines don't move
+                processed.source.push_str("// ");
+                processed.source.push_str(line);
+                processed.source.push('\n');
+                continue;
+            }
+            if stack.contains(&include_name) {
+                let mut chain = stack.clone();
+                chain.push(include_name);
+                return Err(ShaderError::IncludeCycle(chain));
+            }
+
+            let include_file = processed.files.len();
+            processed.files.push(SourceFile {
+                name: include_name.clone(),
+                text: include_text,
+            });
+            processed
+                .source
+                .push_str(&format!("#line 1 {}\n", include_file));
+
+            stack.push(include_name);
+            self.expand(include_file, processed, stack, once)?;
+            stack.pop();
+
+            processed
+                .source
+                .push_str(&format!("#line {} {}\n", i + 2, file));
+        }
+        Ok(())
+    }
+
+    /// Return the name and the source of the included file, None if it isn't found
+    fn resolve(
+        &self,
+        include: &str,
+        relative: bool,
+        from: &str,
+    ) -> Result<Option<(String, String)>, ShaderError> {
+        let mut candidates = Vec::new();
+        if relative {
+            let directory = Path::new(from).parent().unwrap_or(Path::new(""));
+            candidates.push(directory.join(include));
+        }
+        candidates.push(PathBuf::from(include));
+
+        for candidate in &candidates {
+            if let Some(text) = self.virtual_files.get(&normalize(candidate)) {
+                return Ok(Some((normalize(candidate), text.clone())));
+            }
+        }
+
+        if relative {
+            candidates.truncate(1);
+        } else {
+            candidates.clear();
+        }
+        candidates.extend(self.roots.iter().map(|root| root.join(include)));
+        for candidate in candidates {
+            if candidate.is_file() {
+                let name = normalize(&candidate);
+                return Ok(Some((name.clone(), read_source(&name)?)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Return the included file and true if it's relative, `#include "file"`, or false if it's
+/// only searched on the roots, `#include <file>`
+fn parse_include(line: &str) -> Option<(&str, bool)> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let argument = directive.strip_prefix("include")?.trim();
+    if let Some(argument) = argument.strip_prefix('"') {
+        Some((argument.strip_suffix('"')?, true))
+    } else {
+        let argument = argument.strip_prefix('<')?;
+        Some((argument.strip_suffix('>')?, false))
+    }
+}
+
+/// Return the name and the argument of a preprocessor directive, like `("include", "\"a\"")`
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = directive
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(directive.len());
+    Some((&directive[..end], directive[end..].trim()))
+}
+
+fn is_pragma_once(line: &str) -> bool {
+    parse_directive(line).is_some_and(|(name, argument)| name == "pragma" && argument == "once")
+}
+
+/// Return the depth of the `#if 0` block after the line, 0 if the next line is compiled
+fn update_disabled(line: &str, disabled: usize) -> usize {
+    let Some((name, argument)) = parse_directive(line) else {
+        return disabled;
+    };
+    match name {
+        "if" | "ifdef" | "ifndef" if disabled > 0 => disabled + 1,
+        "if" if argument == "0" => 1,
+        "elif" | "else" if disabled == 1 => 0,
+        "endif" => disabled.saturating_sub(1),
+        _ => disabled,
+    }
+}
+
+/// Return true if the line ends inside a `/* */` comment, `in_comment` is true if it starts
+/// inside one
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    let mut rest = line;
+    loop {
+        if in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    in_comment = false;
+                }
+                None => return true,
+            }
+        } else {
+            match (rest.find("/*"), rest.find("//")) {
+                (Some(start), Some(line_comment)) if line_comment < start => return false,
+                (Some(start), _) => {
+                    rest = &rest[start + 2..];
+                    in_comment = true;
+                }
+                (None, _) => return false,
+            }
+        }
+    }
+}
+
+fn is_version(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix('#')
+        .is_some_and(|directive| directive.trim_start().starts_with("version"))
+}
+
+/// Remove the `.` and `..` of the path so the same file always has the same name
+fn normalize(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::ShaderStage;
+
+    fn preprocessor(files: &[(&str, &str)]) -> Preprocessor {
+        let mut preprocessor = Preprocessor::new();
+        for (name, source) in files {
+            preprocessor.add_virtual_file(name, source);
+        }
+        preprocessor
+    }
+
+    fn file_names(processed: &PreprocessedSource) -> Vec<&str> {
+        processed
+            .files
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect()
+    }
+
+    fn with_defines(source: &str, defines: &ShaderDefines) -> String {
+        let mut processed = PreprocessedSource {
+            source: source.to_string(),
+            files: Vec::new(),
+        };
+        processed.insert_defines(defines);
+        processed.source
+    }
+
+    #[test]
+    fn defines_are_inserted_after_the_version() {
+        let defines = ShaderDefines::new()
+            .with_value("MAX_LIGHTS", "8")
+            .unwrap()
+            .with_flag("FOG")
+            .unwrap();
+        assert_eq!(
+            with_defines("// header\n#version 330 core\nvoid main() {}\n", &defines),
+            "// header\n#version 330 core\n#define FOG\n#define MAX_LIGHTS 8\n#line 3 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn defines_without_version_go_first() {
+        let defines = ShaderDefines::new().with_flag("FOG").unwrap();
+        assert_eq!(
+            with_defines("void main() {}", &defines),
+            "#define FOG\n#line 1 0\nvoid main() {}"
+        );
+        assert_eq!(
+            with_defines("#version 450", &defines),
+            "#version 450\n#define FOG\n#line 2 0\n"
+        );
+        assert_eq!(
+            with_defines("#version 450\n", &ShaderDefines::new()),
+            "#version 450\n"
+        );
+    }
+
+    #[test]
+    fn defines_dont_depend_on_the_order() {
+        let mut a = ShaderDefines::new();
+        a.insert("A", "1").unwrap();
+        a.insert("B", "").unwrap();
+        let mut b = ShaderDefines::new();
+        b.insert("B", "").unwrap();
+        b.insert("A", "0").unwrap();
+        assert_ne!(a, b);
+        b.insert("A", "1").unwrap();
+        assert_eq!(a, b);
+
+        let mut variants = HashMap::new();
+        variants.insert(a, 1);
+        assert_eq!(variants.get(&b), Some(&1));
+    }
+
+    #[test]
+    fn invalid_defines_are_rejected() {
+        let mut defines = ShaderDefines::new();
+        for (name, value) in [
+            ("", ""),
+            ("1ST", ""),
+            ("A B", ""),
+            ("A\n#define B", ""),
+            ("A", "1\n#define B 2"),
+            ("A", "1\r"),
+            ("A", "1 \\"),
+        ] {
+            assert!(matches!(
+                defines.insert(name, value),
+                Err(ShaderError::InvalidDefine { .. })
+            ));
+        }
+        assert!(defines.is_empty());
+        assert!(defines.insert("_MAX_LIGHTS2", "(4 * 2)").is_ok());
+    }
+
+    #[test]
+    fn includes_are_surrounded_by_line_directives() {
+        let preprocessor = preprocessor(&[
+            (
+                "shaders/main.frag",
+                "#version 330 core\n#include \"lib/light.glsl\"\nvoid main() {}\n",
+            ),
+            (
+                "shaders/lib/light.glsl",
+                "#version 330 core\nfloat light;\n",
+            ),
+        ]);
+        let processed = preprocessor.process_file("shaders/main.frag").unwrap();
+
+        assert_eq!(
+            processed.source,
+            "#version 330 core\n#line 1 1\n// #version 330 core\nfloat light;\n#line 3 0\nvoid main() {}\n"
+        );
+        assert_eq!(
+            file_names(&processed),
+            ["shaders/main.frag", "shaders/lib/light.glsl"]
+        );
+    }
+
+    #[test]
+    fn relative_includes_fall_back_to_the_name() {
+        let preprocessor = preprocessor(&[
+            ("shaders/main.frag", "#include \"common.glsl\"\n"),
+            ("common.glsl", "float common;\n"),
+        ]);
+        let processed = preprocessor.process_file("shaders/main.frag").unwrap();
+        assert_eq!(file_names(&processed)[1], "common.glsl");
+    }
+
+    #[test]
+    fn parent_directories_are_normalized() {
+        let preprocessor = preprocessor(&[
+            ("shaders/passes/main.frag", "#include \"../lib/./a.glsl\"\n"),
+            ("shaders/lib/a.glsl", "#include \"../common.glsl\"\n"),
+            ("shaders/common.glsl", "float common;\n"),
+        ]);
+        let processed = preprocessor
+            .process_file("./shaders/passes/main.frag")
+            .unwrap();
+        assert_eq!(
+            file_names(&processed),
+            [
+                "shaders/passes/main.frag",
+                "shaders/lib/a.glsl",
+                "shaders/common.glsl"
+            ]
+        );
+        assert_eq!(normalize(Path::new("../a/../../b")), "../../b");
+    }
+
+    #[test]
+    fn angle_includes_are_searched_on_the_roots() {
+        let root = std::env::temp_dir().join(format!("easy-opengl-include-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("noise.glsl"), "float noise;\n").unwrap();
+
+        // A relative file with the same name isn't used by `<>`
+        let mut preprocessor = preprocessor(&[
+            ("shaders/main.frag", "#include <noise.glsl>\n"),
+            ("shaders/noise.glsl", "float wrong;\n"),
+        ]);
+        preprocessor.add_root(&root);
+        let processed = preprocessor.process_file("shaders/main.frag");
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let processed = processed.unwrap();
+        assert_eq!(processed.files[1].name, normalize(&root.join("noise.glsl")));
+        assert!(processed.source.contains("float noise;\n"));
+    }
+
+    #[test]
+    fn missing_include_reports_the_file_and_line() {
+        let preprocessor = preprocessor(&[
+            ("main.frag", "#version 330 core\n#include \"lib.glsl\"\n"),
+            ("lib.glsl", "\n\n#include <missing.glsl>\n"),
+        ]);
+        match preprocessor.process_file("main.frag") {
+            Err(ShaderError::IncludeNotFound {
+                file,
+                line,
+                include,
+            }) => {
+                assert_eq!(file, "lib.glsl");
+                assert_eq!(line, 3);
+                assert_eq!(include, "missing.glsl");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let preprocessor = preprocessor(&[
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "#include \"c.glsl\"\n"),
+            ("c.glsl", "#include \"a.glsl\"\n"),
+        ]);
+        match preprocessor.process_file("a.glsl") {
+            Err(ShaderError::IncludeCycle(chain)) => {
+                assert_eq!(chain, ["a.glsl", "b.glsl", "c.glsl", "a.glsl"])
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pragma_once_files_are_pasted_once() {
+        let preprocessor = preprocessor(&[
+            (
+                "main.frag",
+                "#include \"a.glsl\"\n#include \"b.glsl\"\nvoid main() {}\n",
+            ),
+            ("a.glsl", "#include \"c.glsl\"\n"),
+            ("b.glsl", "#include \"c.glsl\"\n"),
+            ("c.glsl", "#pragma once\nstruct Light { vec3 color; };\n"),
+        ]);
+        let processed = preprocessor.process_file("main.frag").unwrap();
+
+        assert_eq!(processed.source.matches("struct Light").count(), 1);
+        assert_eq!(
+            file_names(&processed),
+            ["main.frag", "a.glsl", "c.glsl", "b.glsl"]
+        );
+        assert!(processed
+            .source
+            .contains("#line 1 3\n// #include \"c.glsl\"\n#line 3 0\n"));
+        assert!(!processed.source.contains("\n#pragma once"));
+    }
+
+    #[test]
+    fn includes_in_comments_and_if_0_are_ignored() {
+        let source = "/* #include \"a.glsl\"\n#include \"b.glsl\" */\n\
+                      #if 0\n#ifdef X\n#endif\n#include \"c.glsl\"\n#else\n\
+                      #include \"d.glsl\"\n#endif\n\
+                      // #include \"e.glsl\"\n";
+        let preprocessor = preprocessor(&[("main.frag", source), ("d.glsl", "float d;\n")]);
+        let processed = preprocessor.process_file("main.frag").unwrap();
+        assert_eq!(file_names(&processed), ["main.frag", "d.glsl"]);
+    }
+
+    #[test]
+    fn diagnostics_are_mapped_to_the_included_file() {
+        let preprocessor = preprocessor(&[
+            ("main.frag", "#version 330 core\n#include \"light.glsl\"\n"),
+            ("light.glsl", "uniform vec3 color;\nfloat light = colour;\n"),
+        ]);
+        let processed = preprocessor.process_file("main.frag").unwrap();
+
+        let mut diagnostics = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            "0:2(16): error: `colour' undeclared\n1:2(16): error: `colour' undeclared",
+            &processed.source,
+        );
+        processed.map_diagnostics(&mut diagnostics);
+
+        assert_eq!(diagnostics[0].file.as_deref(), Some("main.frag"));
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("#include \"light.glsl\"")
+        );
+        assert_eq!(diagnostics[1].file.as_deref(), Some("light.glsl"));
+        assert_eq!(diagnostics[1].line, Some(2));
+        assert_eq!(
+            diagnostics[1].code.as_deref(),
+            Some("float light = colour;")
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "light.glsl:2: error: `colour' undeclared\n    2 | float light = colour;"
+        );
+    }
+}
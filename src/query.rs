@@ -0,0 +1,344 @@
+use std::collections::VecDeque;
+
+/// What a query measures, the value is the gl target of the query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryKind {
+    /// The time in nanoseconds that the gpu spent on the commands
+    TimeElapsed = gl::TIME_ELAPSED as isize,
+    /// The time in nanoseconds of the gpu when it reached the query, it doesn't use
+    /// `begin`/`end`, use `record_timestamp`
+    Timestamp = gl::TIMESTAMP as isize,
+    /// The number of samples that passed the depth and stencil tests
+    SamplesPassed = gl::SAMPLES_PASSED as isize,
+    /// 1 if any sample passed the depth and stencil tests, else 0
+    AnySamplesPassed = gl::ANY_SAMPLES_PASSED as isize,
+    /// Like `AnySamplesPassed` but faster and it can be 1 when no sample passed
+    AnySamplesPassedConservative = gl::ANY_SAMPLES_PASSED_CONSERVATIVE as isize,
+    /// The number of primitives generated by the vertex stages
+    PrimitivesGenerated = gl::PRIMITIVES_GENERATED as isize,
+    /// The number of primitives written to the transform feedback buffers
+    PrimitivesWritten = gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN as isize,
+}
+
+impl QueryKind {
+    /// Return true if the query counts samples, only these queries can be used for conditional
+    /// rendering
+    pub fn is_occlusion(&self) -> bool {
+        matches!(
+            self,
+            QueryKind::SamplesPassed
+                | QueryKind::AnySamplesPassed
+                | QueryKind::AnySamplesPassedConservative
+        )
+    }
+}
+
+/// How the draws of a conditional render wait for the result of the query, the value is the gl
+/// enum given to `glBeginConditionalRender`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionalMode {
+    /// Wait for the result
+    Wait = gl::QUERY_WAIT as isize,
+    /// Don't wait, if the result isn't ready the draws are done
+    NoWait = gl::QUERY_NO_WAIT as isize,
+    /// Wait for the result, the gpu can skip only the regions without samples
+    ByRegionWait = gl::QUERY_BY_REGION_WAIT as isize,
+    ByRegionNoWait = gl::QUERY_BY_REGION_NO_WAIT as isize,
+    /// Like `Wait` but the draws are done only if no sample passed
+    WaitInverted = gl::QUERY_WAIT_INVERTED as isize,
+    NoWaitInverted = gl::QUERY_NO_WAIT_INVERTED as isize,
+    ByRegionWaitInverted = gl::QUERY_BY_REGION_WAIT_INVERTED as isize,
+    ByRegionNoWaitInverted = gl::QUERY_BY_REGION_NO_WAIT_INVERTED as isize,
+}
+
+/// A abstract representation of a query object, it measures the commands sent between `begin`
+/// and `end`. The result is ready some frames later, it can be asked without blocking
+///  # Example
+/// ``` Rust
+/// let timer = Query::new(QueryKind::TimeElapsed);
+/// timer.begin();
+/// mesh.draw();
+/// timer.end();
+///
+/// let query = Query::new(QueryKind::PrimitivesWritten);
+/// query.begin();
+/// feedback.begin(FeedbackPrimitive::Points)?;
+/// gl::DrawArrays(gl::POINTS, 0, 1000);
+/// feedback.end()?;
+/// query.end();
+///
+/// // Later
+/// if let Some(written) = query.try_result() {
+///     println!("{} particles", written);
+/// }
+/// ```
+pub struct Query {
+    id: u32,
+    kind: QueryKind,
+}
+
+impl Query {
+    pub fn new(kind: QueryKind) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenQueries(1, &mut id);
+        }
+        Self { id, kind }
+    }
+
+    /// Start to measure the commands sent after the call
+    ///
+    /// # Panics
+    /// If the query is a `Timestamp`
+    pub fn begin(&self) {
+        assert_ne!(self.kind, QueryKind::Timestamp, "use record_timestamp");
+        unsafe {
+            gl::BeginQuery(self.kind as u32, self.id);
+        }
+    }
+
+    /// Stop to measure
+    ///
+    /// # Panics
+    /// If the query is a `Timestamp`
+    pub fn end(&self) {
+        assert_ne!(self.kind, QueryKind::Timestamp, "use record_timestamp");
+        unsafe {
+            gl::EndQuery(self.kind as u32);
+        }
+    }
+
+    /// Begin the query and end it when the returned scope is dropped
+    ///
+    /// # Example
+    ///
+    /// ``` Rust
+    /// let timer = Query::new(QueryKind::TimeElapsed);
+    /// {
+    ///     let _scope = timer.scope();
+    ///     shadow_pass.draw();
+    /// }
+    /// ```
+    pub fn scope(&self) -> QueryScope<'_> {
+        self.begin();
+        QueryScope { query: self }
+    }
+
+    /// Record the time of the gpu when it reaches this point of the commands
+    ///
+    /// # Panics
+    /// If the query isn't a `Timestamp`
+    pub fn record_timestamp(&self) {
+        assert_eq!(self.kind, QueryKind::Timestamp, "use begin and end");
+        unsafe {
+            gl::QueryCounter(self.id, gl::TIMESTAMP);
+        }
+    }
+
+    /// Draw the commands sent until the returned guard is dropped only if some sample passed
+    /// the query, the gpu decides it without sending the result to the cpu
+    ///
+    /// # Example
+    ///
+    /// ``` Rust
+    /// // Draw the bounding box without writing color or depth
+    /// occlusion.begin();
+    /// bounding_box.draw();
+    /// occlusion.end();
+    ///
+    /// // Draw the real mesh only if the box was visible
+    /// let _condition = occlusion.conditional_render(ConditionalMode::ByRegionNoWait);
+    /// mesh.draw();
+    /// ```
+    ///
+    /// # Panics
+    /// If the query doesn't count samples, see `QueryKind::is_occlusion`
+    pub fn conditional_render(&self, mode: ConditionalMode) -> ConditionalRender<'_> {
+        assert!(
+            self.kind.is_occlusion(),
+            "conditional rendering needs a occlusion query"
+        );
+        unsafe {
+            gl::BeginConditionalRender(self.id, mode as u32);
+        }
+        ConditionalRender { _query: self }
+    }
+
+    /// Return true if the result is ready, it never blocks
+    pub fn is_ready(&self) -> bool {
+        let mut ready = 0;
+        unsafe {
+            gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut ready);
+        }
+        ready != 0
+    }
+
+    /// Return the result if it's ready, it never blocks
+    pub fn try_result(&self) -> Option<u64> {
+        if self.is_ready() {
+            Some(self.result())
+        } else {
+            None
+        }
+    }
+
+    /// Return the result, it blocks until the gpu finish the measured commands
+    pub fn result(&self) -> u64 {
+        let mut result = 0;
+        unsafe {
+            gl::GetQueryObjectui64v(self.id, gl::QUERY_RESULT, &mut result);
+        }
+        result
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.id);
+        }
+    }
+}
+
+/// A active query, it ends the query when it's dropped
+pub struct QueryScope<'a> {
+    query: &'a Query,
+}
+
+impl Drop for QueryScope<'_> {
+    fn drop(&mut self) {
+        self.query.end();
+    }
+}
+
+/// A active conditional render, it ends when it's dropped
+pub struct ConditionalRender<'a> {
+    _query: &'a Query,
+}
+
+impl Drop for ConditionalRender<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::EndConditionalRender();
+        }
+    }
+}
+
+/// A set of queries of the same kind that are reused, each measure has a label to identify the
+/// result. The results are ready some frames later, `poll` returns the ready ones and recycles
+/// their queries without blocking
+///  # Example
+/// ``` Rust
+/// let mut timers = QueryPool::new(QueryKind::TimeElapsed);
+///
+/// loop {
+///     timers.begin("shadows");
+///     shadow_pass.draw();
+///     timers.end();
+///
+///     timers.begin("lighting");
+///     lighting_pass.draw();
+///     timers.end();
+///
+///     for (pass, nanoseconds) in timers.poll() {
+///         println!("{}: {} ms", pass, nanoseconds as f64 / 1e6);
+///     }
+/// }
+/// ```
+pub struct QueryPool<T> {
+    kind: QueryKind,
+    free: Vec<Query>,
+    active: Option<(T, Query)>,
+    pending: VecDeque<(T, Query)>,
+}
+
+impl<T> QueryPool<T> {
+    pub fn new(kind: QueryKind) -> Self {
+        Self {
+            kind,
+            free: Vec::new(),
+            active: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Begin a measure with a recycled query, or a new one if all of them are pending
+    ///
+    /// # Panics
+    /// If a measure is already active or the pool is of `Timestamp` queries
+    pub fn begin(&mut self, label: T) {
+        assert!(
+            self.active.is_none(),
+            "a query of the pool is already active"
+        );
+        let query = self.take_query();
+        query.begin();
+        self.active = Some((label, query));
+    }
+
+    /// End the active measure, the result can be read with `poll` when it's ready
+    pub fn end(&mut self) {
+        if let Some((label, query)) = self.active.take() {
+            query.end();
+            self.pending.push_back((label, query));
+        }
+    }
+
+    /// Record the time of the gpu at this point, only for a pool of `Timestamp` queries
+    ///
+    /// # Panics
+    /// If the pool isn't of `Timestamp` queries
+    pub fn record_timestamp(&mut self, label: T) {
+        let query = self.take_query();
+        query.record_timestamp();
+        self.pending.push_back((label, query));
+    }
+
+    /// Return the results that are ready in the order they were measured, their queries are
+    /// recycled. It never blocks
+    pub fn poll(&mut self) -> Vec<(T, u64)> {
+        let mut results = Vec::new();
+        let mut waiting = VecDeque::new();
+        while let Some((label, query)) = self.pending.pop_front() {
+            match query.try_result() {
+                Some(result) => {
+                    results.push((label, result));
+                    self.free.push(query);
+                }
+                None => waiting.push_back((label, query)),
+            }
+        }
+        self.pending = waiting;
+        results
+    }
+
+    /// The number of measures without result yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The number of queries created by the pool
+    pub fn len(&self) -> usize {
+        self.free.len() + self.pending.len() + self.active.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    fn take_query(&mut self) -> Query {
+        self.free.pop().unwrap_or_else(|| Query::new(self.kind))
+    }
+}
@@ -0,0 +1,218 @@
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use bytemuck::Pod;
+
+use crate::buffers::{
+    capacity_bytes, max_capacity, BufferError, BufferUsage, GpuBuffer, MapFlags, RawBuffer,
+    StorageFlags,
+};
+use crate::sync::Fence;
+
+/// A range of a ring buffer given to the current frame, the values are in elements from the
+/// start of the buffer, so `offset` can be used directly as first vertex of a draw call
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RingAllocation {
+    pub offset: usize,
+    pub count: usize,
+}
+
+/// A buffer for data that is rewritten every frame, like ui or particles. The buffer is split in
+/// one region for each frame in flight, each frame the data is appended on its region without
+/// waiting for the gpu to finish reading the previous frames.
+///
+/// If the driver supports `glBufferStorage` the buffer is mapped persistently and each region is
+/// guarded with a fence, else the buffer is orphaned when the ring wraps and the regions are
+/// written with unsynchronized mappings.
+///  # Example
+/// ``` Rust
+/// // Space for 1000 vertices per frame, with 3 frames in flight
+/// let ring = Rc::new(RingBuffer::<MyVertex>::new(gl::ARRAY_BUFFER, 1000, 3)?);
+/// let mut vao = VertexArray::new();
+/// vao.add_vertex_buffer(ring.clone(), MyVertex::layout());
+///
+/// loop {
+///     ring.begin_frame()?;
+///     let quad = ring.alloc(&quad_vertices)?;
+///     vao.bind();
+///     gl::DrawArrays(gl::TRIANGLE_FAN, quad.offset as i32, quad.count as i32);
+///     ring.end_frame();
+/// }
+/// ```
+pub struct RingBuffer<T: Pod> {
+    raw: RawBuffer,
+    frame_capacity: usize,
+    frames: usize,
+    current: Cell<usize>,
+    cursor: Cell<usize>,
+    fences: RefCell<Vec<Option<Fence>>>,
+    ptr: Option<*mut T>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> RingBuffer<T> {
+    /// Return a ring buffer with `frames` regions of `frame_capacity` elements
+    ///
+    ///  # Arguments
+    ///  * `target` - The gl target, like `gl::ARRAY_BUFFER`
+    ///  * `frame_capacity` - The number of elements that a frame can allocate
+    ///  * `frames` - The number of frames in flight, usually 2 or 3
+    pub fn new(target: u32, frame_capacity: usize, frames: usize) -> Result<Self, BufferError> {
+        let frames = frames.max(1);
+        // The bounds are per frame, a frame can't hold more than its share of the buffer
+        let size = match frame_capacity.checked_mul(frames) {
+            Some(capacity) => capacity_bytes::<T>(capacity)?,
+            None => {
+                return Err(BufferError::OutOfBounds {
+                    offset: 0,
+                    len: frame_capacity,
+                    capacity: max_capacity::<T>() / frames,
+                })
+            }
+        };
+
+        let (raw, ptr) = if gl::BufferStorage::is_loaded() {
+            let raw = RawBuffer::new(
+                target,
+                size,
+                None,
+                BufferUsage::Immutable(
+                    StorageFlags::MAP_WRITE
+                        | StorageFlags::MAP_PERSISTENT
+                        | StorageFlags::MAP_COHERENT,
+                ),
+            )?;
+            let ptr = if size == 0 {
+                None
+            } else {
+                Some(raw.map_bytes(
+                    0,
+                    size,
+                    MapFlags::WRITE | MapFlags::PERSISTENT | MapFlags::COHERENT,
+                )? as *mut T)
+            };
+            (raw, ptr)
+        } else {
+            (
+                RawBuffer::new(target, size, None, BufferUsage::StreamDraw)?,
+                None,
+            )
+        };
+
+        Ok(Self {
+            raw,
+            frame_capacity,
+            frames,
+            current: Cell::new(0),
+            cursor: Cell::new(0),
+            fences: RefCell::new((0..frames).map(|_| None).collect()),
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Return true if the buffer is mapped persistently
+    pub fn is_persistent(&self) -> bool {
+        self.ptr.is_some()
+    }
+
+    /// Start to allocate on the region of the next frame, it waits until the gpu finish
+    /// reading the region
+    pub fn begin_frame(&self) -> Result<(), BufferError> {
+        self.cursor.set(0);
+
+        if self.is_persistent() {
+            if let Some(fence) = self.fences.borrow_mut()[self.current.get()].take() {
+                fence.wait(u64::MAX);
+            }
+        } else if self.current.get() == 0 {
+            // The regions of the new storage aren't used by the gpu, so `alloc` can map them
+            // without sync until the ring wraps again
+            self.raw.orphan()?;
+        }
+        Ok(())
+    }
+
+    /// Copy the data on the region of the current frame
+    pub fn alloc(&self, data: &[T]) -> Result<RingAllocation, BufferError> {
+        let cursor = self.cursor.get();
+        if cursor + data.len() > self.frame_capacity {
+            return Err(BufferError::OutOfBounds {
+                offset: cursor,
+                len: data.len(),
+                capacity: self.frame_capacity,
+            });
+        }
+
+        let offset = self.current.get() * self.frame_capacity + cursor;
+        match self.ptr {
+            Some(ptr) => unsafe {
+                std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.add(offset), data.len());
+            },
+            None => {
+                // `glBufferSubData` would wait for the draws of the previous frames that read
+                // the same storage
+                let bytes: &[u8] = bytemuck::cast_slice(data);
+                let mut mapping = self.raw.map_range::<u8>(
+                    offset * size_of::<T>(),
+                    bytes.len(),
+                    MapFlags::WRITE | MapFlags::UNSYNCHRONIZED | MapFlags::INVALIDATE_RANGE,
+                )?;
+                mapping.copy_from_slice(bytes);
+            }
+        }
+
+        self.cursor.set(cursor + data.len());
+        Ok(RingAllocation {
+            offset,
+            count: data.len(),
+        })
+    }
+
+    /// Finish the current frame, call it after sending the draws that use the allocations
+    pub fn end_frame(&self) {
+        if self.is_persistent() {
+            self.fences.borrow_mut()[self.current.get()] = Some(Fence::new());
+        }
+        self.current.set((self.current.get() + 1) % self.frames);
+    }
+
+    /// The number of elements that a frame can allocate
+    pub fn frame_capacity(&self) -> usize {
+        self.frame_capacity
+    }
+
+    /// The number of elements still free on the current frame
+    pub fn remaining(&self) -> usize {
+        self.frame_capacity - self.cursor.get()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.raw.id()
+    }
+
+    pub fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+
+    pub fn bind(&self) {
+        self.raw.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.raw.unbind();
+    }
+}
+
+impl<T: Pod> GpuBuffer for RingBuffer<T> {
+    fn raw(&self) -> &RawBuffer {
+        &self.raw
+    }
+}
+
+impl<T: Pod> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        self.raw.unmap();
+    }
+}
@@ -1,12 +1,15 @@
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::ptr;
-use std::str;
 
 use gl::types::*;
 
+use crate::preprocessor::{PreprocessedSource, Preprocessor, ShaderDefines};
+use crate::transform_feedback::FeedbackMode;
+
 pub enum UniformType {
     U32(u32),
     I32(i32),
@@ -25,21 +28,278 @@ pub enum UniformType {
     M4(*const f32),
 }
 
+/// The stage of the pipeline that a shader source runs on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    /// Decides how much each patch is subdivided, it needs a `TessEvaluation` stage
+    TessControl,
+    /// Computes the vertices generated by the tessellation of the patches
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    /// It can't be combined with the other stages, see `ComputeProgram`
+    Compute,
+}
+
+impl ShaderStage {
+    /// The gl enum of the stage, like `gl::VERTEX_SHADER`
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::TessControl => write!(f, "tessellation control"),
+            ShaderStage::TessEvaluation => write!(f, "tessellation evaluation"),
+            ShaderStage::Geometry => write!(f, "geometry"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+            ShaderStage::Compute => write!(f, "compute"),
+        }
+    }
+}
+
+/// A line of the compile log of the driver, like `0(12) : error C0000: syntax error`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderStage,
+    /// The source string number, it's 0 unless the source uses `#line` directives
+    pub source: u32,
+    /// The file of the line, set when the source was preprocessed
+    pub file: Option<String>,
+    /// The line of the source, None if the driver didn't say it
+    pub line: Option<u32>,
+    pub message: String,
+    /// The text of the line of the source
+    pub code: Option<String>,
+}
+
+impl ShaderDiagnostic {
+    /// Parse the lines of a compile log. The formats of the main drivers are known, the lines
+    /// that aren't understood are kept without line
+    pub fn parse(stage: ShaderStage, log: &str, source: &str) -> Vec<Self> {
+        log.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (source_number, line_number, message) = match parse_log_line(line) {
+                    Some((source_number, line_number, message)) => {
+                        (source_number, Some(line_number), message)
+                    }
+                    None => (0, None, line.to_string()),
+                };
+                let code = line_number
+                    .filter(|_| source_number == 0)
+                    .and_then(|line| source.lines().nth((line as usize).checked_sub(1)?))
+                    .map(|code| code.to_string());
+                Self {
+                    stage,
+                    source: source_number,
+                    file: None,
+                    line: line_number,
+                    message,
+                    code,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file, line, self.message)?,
+            (_, Some(line)) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.stage, self.source, line, self.message
+            )?,
+            (_, None) => write!(f, "{}: {}", self.stage, self.message)?,
+        }
+        if let (Some(line), Some(code)) = (self.line, &self.code) {
+            write!(f, "\n{:>5} | {}", line, code)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a log line into the source number, the line and the message. It knows the formats
+/// `0(12) : error ...` (nvidia), `0:12(5): error: ...` (mesa) and `ERROR: 0:12: ...` (amd,
+/// intel and apple)
+fn parse_log_line(line: &str) -> Option<(u32, u32, String)> {
+    let (severity, rest) = match line.split_once(": ") {
+        Some((severity @ ("ERROR" | "WARNING"), rest)) => (Some(severity), rest),
+        _ => (None, line),
+    };
+
+    let digits = |text: &str| -> Option<(u32, usize)> {
+        let end = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        Some((text[..end].parse().ok()?, end))
+    };
+
+    let (source, end) = digits(rest)?;
+    let rest = &rest[end..];
+    let (line_number, rest) = if let Some(rest) = rest.strip_prefix('(') {
+        let (line_number, end) = digits(rest)?;
+        (line_number, rest[end..].strip_prefix(')')?)
+    } else {
+        let rest = rest.strip_prefix(':')?;
+        let (line_number, end) = digits(rest)?;
+        let mut rest = &rest[end..];
+        // The column of mesa
+        if let Some(column) = rest.strip_prefix('(') {
+            rest = &column[column.find(')')? + 1..];
+        }
+        (line_number, rest)
+    };
+
+    let message = rest.trim_start_matches([' ', ':']).to_string();
+    let message = match severity {
+        Some(severity) => format!("{}: {}", severity.to_lowercase(), message),
+        None => message,
+    };
+    Some((source, line_number, message))
+}
+
+/// Errors returned when a shader is loaded
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The file of a source couldn't be read
+    Io { path: String, error: io::Error },
+    /// The source of a stage didn't compile, `log` is the whole log of the driver
+    Compile {
+        stage: ShaderStage,
+        log: String,
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+    /// The stages compiled but the program didn't link
+    Link { log: String },
+    /// The source of a stage has a nul byte
+    InvalidSource(ShaderStage),
+    /// The stages can't be linked together, like a compute stage with a vertex stage
+    InvalidStages(&'static str),
+    /// The file included on the `line` of `file` wasn't found
+    IncludeNotFound {
+        file: String,
+        line: u32,
+        include: String,
+    },
+    /// The files include each other, the last file of the chain is already included by the
+    /// first one
+    IncludeCycle(Vec<String>),
+    /// The define can't be inserted on a source
+    InvalidDefine { name: String, reason: &'static str },
+    /// The name of a transform feedback varying has a nul byte
+    InvalidFeedbackVarying(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => write!(f, "couldn't read {}: {}", path, error),
+            ShaderError::Compile {
+                stage,
+                log,
+                diagnostics,
+            } => {
+                write!(f, "couldn't compile the {} shader", stage)?;
+                if diagnostics.is_empty() {
+                    return write!(f, ": {}", log.trim_end());
+                }
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { log } => {
+                write!(f, "couldn't link the program: {}", log.trim_end())
+            }
+            ShaderError::InvalidSource(stage) => {
+                write!(f, "the source of the {} shader has a nul byte", stage)
+            }
+            ShaderError::InvalidStages(reason) => {
+                write!(f, "invalid combination of stages: {}", reason)
+            }
+            ShaderError::IncludeNotFound {
+                file,
+                line,
+                include,
+            } => write!(
+                f,
+                "{}:{}: couldn't find the include {}",
+                file, line, include
+            ),
+            ShaderError::IncludeCycle(chain) => {
+                write!(f, "cyclic include: {}", chain.join(" -> "))
+            }
+            ShaderError::InvalidDefine { name, reason } => {
+                write!(f, "invalid define {:?}: {}", name, reason)
+            }
+            ShaderError::InvalidFeedbackVarying(name) => {
+                write!(f, "the feedback varying {:?} has a nul byte", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A vertex attribute declared by a linked shader
+pub struct ActiveAttrib {
+    pub name: String,
+    pub location: u32,
+    /// The gl type of the attribute, like `gl::FLOAT_VEC3`
+    pub gl_type: u32,
+    /// The number of elements if the attribute is a array
+    pub size: i32,
+}
+
 /// A abstract representation of a shader
 ///  # Example
 /// ``` Rust
 ///
 /// let mut shader1 = Shader::new();
-/// shader1.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None);
+/// shader1.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None)?;
 ///
 /// let mut shader2 = Shader::new();
-/// shader2.load_from_file("./shaders/vertext.glsl", "./shaders/fragment.glsl", None);
+/// if let Err(err) = shader2.load_from_file("./shaders/vertext.glsl", "./shaders/fragment.glsl", None) {
+///     // couldn't compile the fragment shader
+///     // fragment:0:12: error C1008: undefined variable "colour"
+///     //    12 |     FragColor = colour;
+///     println!("{}", err);
+/// }
 ///
 /// shader1.set_uniform_int("entity_id", 33);
 /// ```
 pub struct Shader {
     pub program: u32,
     pub uniforms_location: HashMap<String, i32>,
+    feedback_varyings: Option<(Vec<String>, FeedbackMode)>,
+}
+
+impl Default for Shader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Shader {
@@ -47,9 +307,26 @@ impl Shader {
         Self {
             program: 0,
             uniforms_location: HashMap::new(),
+            feedback_varyings: None,
         }
     }
 
+    /// Capture the outputs `varyings` of the last vertex stage with a `TransformFeedback`, it
+    /// has to be called before loading the shader because the varyings are set on the link. A
+    /// name with a nul byte makes the load fail
+    ///
+    /// # Example
+    ///
+    /// ``` Rust
+    /// let mut shader = Shader::new();
+    /// shader.set_feedback_varyings(&["outPosition", "outVelocity"], FeedbackMode::Separate);
+    /// shader.load_from_memory(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE, None);
+    /// ```
+    pub fn set_feedback_varyings(&mut self, varyings: &[&str], mode: FeedbackMode) {
+        let varyings = varyings.iter().map(|varying| varying.to_string()).collect();
+        self.feedback_varyings = Some((varyings, mode));
+    }
+
     pub fn bind(&self) {
         unsafe { gl::UseProgram(self.program) }
     }
@@ -63,22 +340,16 @@ impl Shader {
         vertex_shader: &str,
         fragment_shader: &str,
         geo_shader: Option<&String>,
-    ) -> bool {
-        let mut fail = false;
-
-        let vertex = self.compile_shader(vertex_shader, 0, &mut fail);
-        let fragment = self.compile_shader(fragment_shader, 1, &mut fail);
-        let mut geo = None;
+    ) -> Result<(), ShaderError> {
+        let mut sources = vec![
+            (ShaderStage::Vertex, vertex_shader),
+            (ShaderStage::Fragment, fragment_shader),
+        ];
         if let Some(geo_shader) = geo_shader {
-            geo = Some(self.compile_shader(geo_shader, 1, &mut fail));
+            sources.push((ShaderStage::Geometry, geo_shader.as_str()));
         }
 
-        let program = self.create_shader_program(&vertex, &fragment, &geo, &mut fail);
-        self.delete_shaders(&vertex, &fragment, &geo);
-
-        self.program = program;
-
-        fail
+        self.load_stages(&sources)
     }
 
     pub fn load_from_file(
@@ -86,35 +357,69 @@ impl Shader {
         vertex_shader: &str,
         fragment_shader: &str,
         geo_shader: Option<&String>,
-    ) -> bool {
-        static read_files: fn(filename: &str) -> String = |filename: &str| -> String {
-            let mut file = File::open(filename)
-                .expect(format!("Couldn't open the file {}", filename).as_str());
-            let mut source = String::new();
-            file.read_to_string(&mut source)
-                .expect("Couldn't read the file");
-            source
-        };
+    ) -> Result<(), ShaderError> {
+        // The relative includes of the files are resolved
+        let preprocessor = Preprocessor::new();
+        let mut sources = vec![
+            (
+                ShaderStage::Vertex,
+                preprocessor.process_file(vertex_shader)?,
+            ),
+            (
+                ShaderStage::Fragment,
+                preprocessor.process_file(fragment_shader)?,
+            ),
+        ];
+        if let Some(geo_shader) = geo_shader {
+            sources.push((
+                ShaderStage::Geometry,
+                preprocessor.process_file(geo_shader)?,
+            ));
+        }
 
-        let mut fail = false;
-        let vertex_source = read_files(vertex_shader);
-        let vertex = self.compile_shader(vertex_source.as_str(), 0, &mut fail);
+        self.load_preprocessed(&sources)
+    }
 
-        let fragment_source = read_files(fragment_shader);
-        let fragment = self.compile_shader(fragment_source.as_str(), 1, &mut fail);
+    /// Load a program with only a compute stage, see `ComputeProgram` to dispatch it
+    pub fn load_compute_from_memory(&mut self, compute_shader: &str) -> Result<(), ShaderError> {
+        self.load_stages(&[(ShaderStage::Compute, compute_shader)])
+    }
 
-        let mut geo = None;
-        if let Some(geo_shader) = geo_shader {
-            let geo_source = read_files(geo_shader);
-            geo = Some(self.compile_shader(geo_source.as_str(), 1, &mut fail));
+    pub fn load_compute_from_file(&mut self, compute_shader: &str) -> Result<(), ShaderError> {
+        let compute_source = Preprocessor::new().process_file(compute_shader)?;
+        self.load_preprocessed(&[(ShaderStage::Compute, compute_source)])
+    }
+
+    /// Compile the source of each stage and link them, the combination of stages is checked
+    /// before compiling. The shader keeps the program only if everything worked, see
+    /// `ShaderBuilder`
+    pub fn load_stages(&mut self, sources: &[(ShaderStage, &str)]) -> Result<(), ShaderError> {
+        validate_stages(sources)?;
+
+        let mut shaders = Vec::new();
+        for (stage, source) in sources {
+            match self.compile_shader(source, *stage) {
+                Ok(id) => shaders.push(id),
+                Err(err) => {
+                    self.delete_shaders(&shaders);
+                    return Err(err);
+                }
+            }
         }
 
-        let program = self.create_shader_program(&vertex, &fragment, &geo, &mut fail);
-        self.delete_shaders(&vertex, &fragment, &geo);
+        let program = self.create_shader_program(&shaders);
+        self.delete_shaders(&shaders);
+        let program = program?;
 
+        // The previous program is replaced, its uniform locations aren't valid anymore
+        if self.program != 0 {
+            unsafe {
+                gl::DeleteProgram(self.program);
+            }
+        }
         self.program = program;
-
-        fail
+        self.uniforms_location.clear();
+        Ok(())
     }
 
     pub fn set_uniform(&mut self, name: &str, v: UniformType) {
@@ -167,6 +472,64 @@ impl Shader {
         }
     }
 
+    /// Return the location of a vertex attribute, None if the shader doesn't have it
+    pub fn get_attrib_location(&self, name: &str) -> Option<u32> {
+        let c_name = CString::new(name.as_bytes()).ok()?;
+        let location = unsafe { gl::GetAttribLocation(self.program, c_name.as_ptr()) };
+        if location < 0 {
+            None
+        } else {
+            Some(location as u32)
+        }
+    }
+
+    /// Return the vertex attributes used by the shader, the built-in ones like `gl_VertexID` are
+    /// ignored
+    pub fn active_attribs(&self) -> Vec<ActiveAttrib> {
+        let mut attribs = Vec::new();
+        unsafe {
+            let mut count = 0;
+            let mut max_length = 0;
+            gl::GetProgramiv(self.program, gl::ACTIVE_ATTRIBUTES, &mut count);
+            gl::GetProgramiv(
+                self.program,
+                gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+                &mut max_length,
+            );
+
+            let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+            for i in 0..count {
+                let mut length = 0;
+                let mut size = 0;
+                let mut gl_type = 0;
+                gl::GetActiveAttrib(
+                    self.program,
+                    i as u32,
+                    name_buffer.len() as i32,
+                    &mut length,
+                    &mut size,
+                    &mut gl_type,
+                    name_buffer.as_mut_ptr() as *mut GLchar,
+                );
+
+                let name = String::from_utf8_lossy(&name_buffer[..length as usize]).to_string();
+                if name.starts_with("gl_") {
+                    continue;
+                }
+
+                if let Some(location) = self.get_attrib_location(&name) {
+                    attribs.push(ActiveAttrib {
+                        name,
+                        location,
+                        gl_type,
+                        size,
+                    });
+                }
+            }
+        }
+        attribs
+    }
+
     fn get_uniform_locacion(&mut self, name: &str) -> i32 {
         if self.uniforms_location.contains_key(name) {
             return self.uniforms_location[name];
@@ -179,102 +542,352 @@ impl Shader {
         self.uniforms_location[name]
     }
 
-    fn create_shader_program(
-        &self,
-        vertex: &u32,
-        fragment: &u32,
-        geo: &Option<u32>,
-        fail: &mut bool,
-    ) -> u32 {
+    // Like `load_stages`, the lines of the compile errors are mapped to the included files
+    fn load_preprocessed(
+        &mut self,
+        sources: &[(ShaderStage, PreprocessedSource)],
+    ) -> Result<(), ShaderError> {
+        let stages: Vec<(ShaderStage, &str)> = sources
+            .iter()
+            .map(|(stage, source)| (*stage, source.source.as_str()))
+            .collect();
+
+        self.load_stages(&stages).map_err(|mut err| {
+            if let ShaderError::Compile {
+                stage, diagnostics, ..
+            } = &mut err
+            {
+                if let Some((_, source)) = sources.iter().find(|(other, _)| other == stage) {
+                    source.map_diagnostics(diagnostics);
+                }
+            }
+            err
+        })
+    }
+
+    fn create_shader_program(&self, shaders: &[u32]) -> Result<u32, ShaderError> {
+        let feedback = match &self.feedback_varyings {
+            Some((varyings, mode)) => {
+                let varyings = varyings
+                    .iter()
+                    .map(|varying| {
+                        CString::new(varying.as_bytes())
+                            .map_err(|_| ShaderError::InvalidFeedbackVarying(varying.clone()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some((varyings, *mode))
+            }
+            None => None,
+        };
+
         unsafe {
             let program = gl::CreateProgram();
-            gl::AttachShader(program, *vertex);
-            gl::AttachShader(program, *fragment);
-            if let Some(geo) = geo {
-                gl::AttachShader(program, *geo);
+            for shader in shaders {
+                gl::AttachShader(program, *shader);
+            }
+            if let Some((varyings, mode)) = &feedback {
+                let names: Vec<*const GLchar> =
+                    varyings.iter().map(|varying| varying.as_ptr()).collect();
+                gl::TransformFeedbackVaryings(
+                    program,
+                    names.len() as i32,
+                    names.as_ptr(),
+                    *mode as u32,
+                );
             }
             gl::LinkProgram(program);
 
             let mut success = 0;
-            let mut info_log = Vec::with_capacity(512);
-            info_log.set_len(512 - 1);
-
             gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
             if success != gl::TRUE as i32 {
-                *fail |= true;
-                gl::GetProgramInfoLog(
-                    program,
-                    512,
-                    ptr::null_mut(),
-                    info_log.as_mut_ptr() as *mut GLchar,
-                );
+                let log = info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog);
+                gl::DeleteProgram(program);
+                return Err(ShaderError::Link { log });
             }
-            program
+            Ok(program)
         }
     }
 
-    fn compile_shader(&self, shader: &str, _type: u8, fail: &mut bool) -> u32 {
-        unsafe {
-            let id = gl::CreateShader(self.get_shader_type(&_type));
+    fn compile_shader(&self, shader: &str, stage: ShaderStage) -> Result<u32, ShaderError> {
+        let c_str_shader =
+            CString::new(shader.as_bytes()).map_err(|_| ShaderError::InvalidSource(stage))?;
 
-            let c_str_shader = CString::new(shader.as_bytes()).unwrap();
-            gl::ShaderSource(id, 1, &c_str_shader.as_ptr(), ptr::null());
+        unsafe {
+            let id = gl::CreateShader(stage.gl_type());
+            gl::ShaderSource(id, 1, &c_str_shader.as_ptr(), std::ptr::null());
             gl::CompileShader(id);
 
             let mut success = 0;
-            let mut info_log = Vec::with_capacity(512);
-            info_log.set_len(512 - 1);
-
             gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as i32 {
-                *fail |= true;
-
-                gl::GetShaderInfoLog(
-                    id,
-                    512,
-                    ptr::null_mut(),
-                    info_log.as_mut_ptr() as *mut GLchar,
-                );
-
-                println!(
-                    "Compiling {} shader fail. Error: {}",
-                    self.get_shader_name(&_type),
-                    str::from_utf8(&info_log).unwrap()
-                );
+                let log = info_log(id, gl::GetShaderiv, gl::GetShaderInfoLog);
+                gl::DeleteShader(id);
+                return Err(ShaderError::Compile {
+                    stage,
+                    diagnostics: ShaderDiagnostic::parse(stage, &log, shader),
+                    log,
+                });
             }
 
-            id
+            Ok(id)
         }
     }
 
-    fn delete_shaders(&self, vertex: &u32, fragment: &u32, geo: &Option<u32>) {
+    fn delete_shaders(&self, shaders: &[u32]) {
         unsafe {
-            gl::DeleteShader(*vertex);
-            gl::DeleteShader(*fragment);
-            if let Some(geo) = geo {
-                gl::DeleteShader(*geo);
+            for shader in shaders {
+                gl::DeleteShader(*shader);
             }
         }
     }
+}
 
-    fn get_shader_type(&self, _type: &u8) -> u32 {
-        if *_type == 0 {
-            gl::VERTEX_SHADER
-        } else if *_type == 1 {
-            gl::FRAGMENT_SHADER
-        } else {
-            gl::GEOMETRY_SHADER
+/// Check that the stages can be linked together
+fn validate_stages(sources: &[(ShaderStage, &str)]) -> Result<(), ShaderError> {
+    let has = |stage: ShaderStage| sources.iter().any(|(other, _)| *other == stage);
+
+    if sources.is_empty() {
+        return Err(ShaderError::InvalidStages(
+            "the program doesn't have stages",
+        ));
+    }
+    for (i, (stage, _)) in sources.iter().enumerate() {
+        if sources[..i].iter().any(|(other, _)| other == stage) {
+            return Err(ShaderError::InvalidStages("a stage is repeated"));
         }
     }
+    if has(ShaderStage::Compute) {
+        if sources.len() > 1 {
+            return Err(ShaderError::InvalidStages(
+                "a compute stage can't be linked with other stages",
+            ));
+        }
+        return Ok(());
+    }
+    if !has(ShaderStage::Vertex) {
+        return Err(ShaderError::InvalidStages("a vertex stage is needed"));
+    }
+    if has(ShaderStage::TessControl) && !has(ShaderStage::TessEvaluation) {
+        return Err(ShaderError::InvalidStages(
+            "a tessellation control stage needs a tessellation evaluation stage",
+        ));
+    }
+    Ok(())
+}
 
-    fn get_shader_name(&self, _type: &u8) -> &str {
-        if *_type == 0 {
-            "vertex"
-        } else if *_type == 1 {
-            "fragment"
-        } else {
-            "geometry"
+pub(crate) fn read_source(filename: &str) -> Result<String, ShaderError> {
+    let mut source = String::new();
+    File::open(filename)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|error| ShaderError::Io {
+            path: filename.to_string(),
+            error,
+        })?;
+    Ok(source)
+}
+
+/// Read the whole info log of a shader or a program, `get_iv` and `get_log` are the gl functions
+/// of the object like `gl::GetShaderiv` and `gl::GetShaderInfoLog`
+unsafe fn info_log(
+    id: u32,
+    get_iv: unsafe fn(u32, GLenum, *mut GLint),
+    get_log: unsafe fn(u32, GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut length = 0;
+    get_iv(id, gl::INFO_LOG_LENGTH, &mut length);
+
+    let mut log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    get_log(
+        id,
+        log.len() as i32,
+        &mut written,
+        log.as_mut_ptr() as *mut GLchar,
+    );
+    log.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&log).to_string()
+}
+
+/// Where the source of a stage comes from
+#[derive(Clone)]
+enum StageSource {
+    Memory(String),
+    File(String),
+}
+
+/// A builder of shaders with any combination of stages, the sources can be strings or files.
+/// The combination is checked when it's built:
+/// * A compute stage can't have other stages
+/// * Else a vertex stage is needed
+/// * A tessellation control stage needs a tessellation evaluation stage
+///
+///  # Example
+/// ``` Rust
+/// let terrain = ShaderBuilder::new()
+///     .with_source(ShaderStage::Vertex, TERRAIN_VERTEX_SOURCE)
+///     .with_file(ShaderStage::TessControl, "./shaders/terrain.tesc")
+///     .with_file(ShaderStage::TessEvaluation, "./shaders/terrain.tese")
+///     .with_source(ShaderStage::Fragment, TERRAIN_FRAGMENT_SOURCE)
+///     .build()?;
+///
+/// DrawCall::arrays(Topology::Patches(4), 0..patches * 4).submit(&vao)?;
+///
+/// let normals = ShaderBuilder::new()
+///     .with_source(ShaderStage::Vertex, VERTEX_SOURCE)
+///     .with_source(ShaderStage::Geometry, NORMALS_GEOMETRY_SOURCE)
+///     .with_source(ShaderStage::Fragment, FRAGMENT_SOURCE)
+///     .build()?;
+/// ```
+#[derive(Clone, Default)]
+pub struct ShaderBuilder {
+    stages: Vec<(ShaderStage, StageSource)>,
+    preprocessor: Preprocessor,
+    defines: ShaderDefines,
+    /// The first define rejected by `with_define`, it's reported by `build`
+    invalid_define: Option<(String, &'static str)>,
+    feedback_varyings: Option<(Vec<String>, FeedbackMode)>,
+}
+
+impl ShaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stage with its source
+    pub fn with_source(mut self, stage: ShaderStage, source: &str) -> Self {
+        self.stages
+            .push((stage, StageSource::Memory(source.to_string())));
+        self
+    }
+
+    /// Add a stage with the source of the file `filepath`, the file is read on `build`
+    pub fn with_file(mut self, stage: ShaderStage, filepath: &str) -> Self {
+        self.stages
+            .push((stage, StageSource::File(filepath.to_string())));
+        self
+    }
+
+    /// Resolve the includes of the sources with `preprocessor`, by default only the includes
+    /// relative to the files are found
+    pub fn with_preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    /// Insert `#define name value` on all the stages after their `#version`, an empty value
+    /// defines a flag. A invalid define is reported by `build`, see `ShaderDefines::insert`
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        if let Err(ShaderError::InvalidDefine { name, reason }) = self.defines.insert(name, value) {
+            self.invalid_define.get_or_insert((name, reason));
+        }
+        self
+    }
+
+    /// Insert the defines on all the stages after their `#version`
+    pub fn with_defines(mut self, defines: &ShaderDefines) -> Self {
+        self.defines.extend(defines);
+        self
+    }
+
+    /// Capture the outputs `varyings`, see `Shader::set_feedback_varyings`
+    pub fn with_feedback_varyings(mut self, varyings: &[&str], mode: FeedbackMode) -> Self {
+        let varyings = varyings.iter().map(|varying| varying.to_string()).collect();
+        self.feedback_varyings = Some((varyings, mode));
+        self
+    }
+
+    /// Read the files, resolve the includes, compile the stages and link them
+    pub fn build(&self) -> Result<Shader, ShaderError> {
+        if let Some((name, reason)) = &self.invalid_define {
+            return Err(ShaderError::InvalidDefine {
+                name: name.clone(),
+                reason,
+            });
+        }
+
+        let mut sources = Vec::new();
+        for (stage, source) in &self.stages {
+            let mut source = match source {
+                StageSource::Memory(source) => {
+                    self.preprocessor.process(&stage.to_string(), source)?
+                }
+                StageSource::File(filepath) => self.preprocessor.process_file(filepath)?,
+            };
+            source.insert_defines(&self.defines);
+            sources.push((*stage, source));
+        }
+
+        let mut shader = Shader::new();
+        if let Some((varyings, mode)) = &self.feedback_varyings {
+            let varyings: Vec<&str> = varyings.iter().map(String::as_str).collect();
+            shader.set_feedback_varyings(&varyings, *mode);
+        }
+
+        shader.load_preprocessed(&sources)?;
+        Ok(shader)
+    }
+}
+
+/// A cache of the variants of a shader, each set of defines is compiled the first time it's asked
+/// and reused after. Useful for a uber shader with features that can be toggled
+///  # Example
+/// ``` Rust
+/// let mut variants = ShaderVariants::new(
+///     ShaderBuilder::new()
+///         .with_file(ShaderStage::Vertex, "./shaders/uber.vert")
+///         .with_file(ShaderStage::Fragment, "./shaders/uber.frag"),
+/// );
+///
+/// let mut defines = ShaderDefines::new().with_flag("NORMAL_MAP")?;
+/// if mesh.skinned {
+///     defines.insert("SKINNING", "")?;
+/// }
+///
+/// let shader = variants.get(&defines)?;
+/// shader.bind();
+/// shader.set_uniform("fog_density", UniformType::F32(0.1));
+/// ```
+pub struct ShaderVariants {
+    builder: ShaderBuilder,
+    shaders: HashMap<ShaderDefines, Shader>,
+}
+
+impl ShaderVariants {
+    /// Return a empty cache, `builder` has the stages shared by all the variants
+    pub fn new(builder: ShaderBuilder) -> Self {
+        Self {
+            builder,
+            shaders: HashMap::new(),
+        }
+    }
+
+    /// Return the variant with the `defines` added to the ones of the builder, it's compiled if
+    /// it's the first time. The failed variants aren't cached
+    pub fn get(&mut self, defines: &ShaderDefines) -> Result<&mut Shader, ShaderError> {
+        if !self.shaders.contains_key(defines) {
+            let shader = self.builder.clone().with_defines(defines).build()?;
+            self.shaders.insert(defines.clone(), shader);
         }
+        Ok(self.shaders.get_mut(defines).unwrap())
+    }
+
+    /// Return true if the variant was already compiled
+    pub fn contains(&self, defines: &ShaderDefines) -> bool {
+        self.shaders.contains_key(defines)
+    }
+
+    /// The number of variants compiled
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shaders.is_empty()
+    }
+
+    /// Delete all the variants, they are compiled again when they are asked
+    pub fn clear(&mut self) {
+        self.shaders.clear();
     }
 }
 
@@ -285,3 +898,96 @@ impl Drop for Shader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "#version 330 core\nout vec4 FragColor;\nvoid main() {\n    FragColor = colour;\n}";
+
+    #[test]
+    fn parse_nvidia_log() {
+        assert_eq!(
+            parse_log_line("0(12) : error C1008: undefined variable \"colour\""),
+            Some((
+                0,
+                12,
+                "error C1008: undefined variable \"colour\"".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_mesa_log() {
+        assert_eq!(
+            parse_log_line("0:12(5): error: `colour' undeclared"),
+            Some((0, 12, "error: `colour' undeclared".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_amd_intel_log() {
+        assert_eq!(
+            parse_log_line("ERROR: 0:12: 'colour' : undeclared identifier"),
+            Some((0, 12, "error: 'colour' : undeclared identifier".to_string()))
+        );
+        assert_eq!(
+            parse_log_line("WARNING: 2:7: unused variable"),
+            Some((2, 7, "warning: unused variable".to_string()))
+        );
+    }
+
+    #[test]
+    fn summary_lines_arent_parsed() {
+        assert_eq!(
+            parse_log_line("ERROR: 1 compilation errors.  No code generated."),
+            None
+        );
+        assert_eq!(parse_log_line("Compile failed."), None);
+
+        let diagnostics = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            "ERROR: 0:4: 'colour' : undeclared identifier\nERROR: 1 compilation errors.\n\n",
+            SOURCE,
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].line, None);
+        assert_eq!(diagnostics[1].code, None);
+        assert_eq!(diagnostics[1].message, "ERROR: 1 compilation errors.");
+    }
+
+    #[test]
+    fn diagnostics_have_the_source_line() {
+        let diagnostics = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            "0(4) : error C1008: undefined variable \"colour\"",
+            SOURCE,
+        );
+        assert_eq!(diagnostics[0].stage, ShaderStage::Fragment);
+        assert_eq!(diagnostics[0].line, Some(4));
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("    FragColor = colour;")
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "fragment:0:4: error C1008: undefined variable \"colour\"\n    4 |     FragColor = colour;"
+        );
+    }
+
+    #[test]
+    fn out_of_range_lines_have_no_code() {
+        let diagnostics = ShaderDiagnostic::parse(
+            ShaderStage::Vertex,
+            "0:0(1): error: bad\n0:99(1): error: bad\n1:2(1): error: other string",
+            SOURCE,
+        );
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.code.is_none()));
+        assert_eq!(diagnostics[1].line, Some(99));
+        assert_eq!(diagnostics[2].source, 1);
+    }
+}
@@ -0,0 +1,68 @@
+use gl::types::GLsync;
+
+/// A abstract representation of a fence sync object, it's signaled when the gpu finish all the
+/// commands sent before the fence was created
+///  # Example
+/// ``` Rust
+/// gl::DrawArrays(gl::TRIANGLES, 0, 3);
+/// let fence = Fence::new();
+///
+/// // Don't block, only ask
+/// if fence.is_signaled() {
+///     println!("The draw is done");
+/// }
+///
+/// // Block until the draw is done
+/// fence.wait(u64::MAX);
+/// ```
+pub struct Fence {
+    sync: GLsync,
+}
+
+impl Default for Fence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fence {
+    /// Insert a fence after the commands already sent to the gpu
+    pub fn new() -> Self {
+        Self {
+            sync: unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) },
+        }
+    }
+
+    /// Return true if the gpu already passed the fence, it never blocks
+    pub fn is_signaled(&self) -> bool {
+        let mut status = 0;
+        unsafe {
+            gl::GetSynciv(
+                self.sync,
+                gl::SYNC_STATUS,
+                1,
+                std::ptr::null_mut(),
+                &mut status,
+            );
+        }
+        status == gl::SIGNALED as i32
+    }
+
+    /// Block until the gpu pass the fence or the timeout expires, return true if the fence was
+    /// signaled
+    ///
+    ///  # Arguments
+    ///  * `timeout` - The max time to wait in nanoseconds
+    pub fn wait(&self, timeout: u64) -> bool {
+        let result = unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        result == gl::ALREADY_SIGNALED || result == gl::CONDITION_SATISFIED
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+        }
+    }
+}
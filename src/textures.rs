@@ -1,5 +1,9 @@
 use std::ffi::c_void;
 use std::ffi::CString;
+use std::fmt;
+
+use crate::buffers::{BufferError, PixelBuffer};
+use crate::layout::align_up;
 
 #[derive(Copy, Clone)]
 pub enum TextureParam {
@@ -16,6 +20,60 @@ pub enum TextureFormat {
     Red = gl::RED as isize,
 }
 
+impl TextureFormat {
+    /// The number of bytes of a pixel
+    fn channels(&self) -> usize {
+        match self {
+            TextureFormat::Rgba => 4,
+            TextureFormat::Rgb => 3,
+            TextureFormat::Red => 1,
+        }
+    }
+
+    /// The size in bytes of the pixels of a `width` x `height` region, each row starts aligned
+    /// to 4 bytes like the default pack and unpack alignment of gl. The last row isn't padded
+    fn image_size(&self, width: u32, height: u32) -> usize {
+        if height == 0 {
+            return 0;
+        }
+        let row = width as usize * self.channels();
+        align_up(row, 4) * (height as usize - 1) + row
+    }
+}
+
+/// Errors returned by the transfers between textures and pixel buffers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureError {
+    /// The texture wasn't generated or loaded yet
+    NotCreated,
+    /// The pixels don't fit in the pixel buffer or it can't be used
+    Buffer(BufferError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::NotCreated => write!(f, "the texture wasn't created"),
+            TextureError::Buffer(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Buffer(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<BufferError> for TextureError {
+    fn from(error: BufferError) -> Self {
+        TextureError::Buffer(error)
+    }
+}
+
 // pub enum PixelDataType {
 //     I8 = gl::BYTE as isize,
 //     U8 = gl::UNSIGNED_BYTE as isize,
@@ -36,6 +94,12 @@ pub struct TextureConfig {
     bitmap: bool,
 }
 
+impl Default for TextureConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TextureConfig {
     pub fn new() -> Self {
         Self {
@@ -56,15 +120,16 @@ impl TextureConfig {
 /// ``` Rust
 /// let mut texture1 = Texture2D::new();
 /// texture1.load_from_file("./src/a.png", TextureConfig::new());
-/// texture1.send_data(30, 30, 1, 1, 0xFF000000 as ptr); // Set a red pixel on x: 30, y: 30
+/// // Set a red pixel on x: 30, y: 30
+/// texture1.send_pixels(30, 30, 1, 1, &[255, 0, 0, 255])?;
 ///
 /// let data = vec![...];
 /// let texture2 = Texture2D::new();
 /// texture2.gen_texture(TextureConfig::new());
-/// texture2.send_data(0, 0, 100, 200, data as ptr);
+/// texture2.send_pixels(0, 0, 100, 200, &data)?;
 ///
 /// let texture3 = Texture2D::new();
-/// texture3.load_from_memory(data as ptr, TextureConfig::new());
+/// texture3.load_from_pixels(100, 200, &data, TextureConfig::new())?;
 /// ```
 pub struct Texture2D {
     pub id: u32,
@@ -73,6 +138,12 @@ pub struct Texture2D {
     pub config: Option<TextureConfig>,
 }
 
+impl Default for Texture2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Texture2D {
     pub fn new() -> Self {
         Self {
@@ -109,8 +180,12 @@ impl Texture2D {
         }
     }
 
-    // Send data on a already allocated texture with the config of the generated texture
-    pub fn send_data(
+    /// Send data on a already allocated texture with the config of the generated texture
+    ///
+    /// # Safety
+    /// `data` has to point to the pixels of a `width` x `height` region with the format of the
+    /// texture, each row aligned to 4 bytes
+    pub unsafe fn send_data(
         &self,
         xoffset: u32,
         yoffset: u32,
@@ -140,8 +215,36 @@ impl Texture2D {
         }
     }
 
-    // Generate and allocate a texture with the given data
-    pub fn load_from_memory(
+    /// Send `pixels` on a already allocated texture, like `send_data` but the length of the
+    /// pixels is checked against the region
+    pub fn send_pixels(
+        &self,
+        xoffset: u32,
+        yoffset: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        let config = self.config.as_ref().ok_or(TextureError::NotCreated)?;
+        check_pixels_len(config.format.image_size(width, height), pixels)?;
+        unsafe {
+            self.send_data(
+                xoffset,
+                yoffset,
+                width,
+                height,
+                pixels.as_ptr() as *const c_void,
+            );
+        }
+        Ok(())
+    }
+
+    /// Generate and allocate a texture with the given data
+    ///
+    /// # Safety
+    /// `data` has to be null or point to the pixels of a `width` x `height` image with the
+    /// format of the config, each row aligned to 4 bytes
+    pub unsafe fn load_from_memory(
         &mut self,
         width: u32,
         height: u32,
@@ -186,6 +289,22 @@ impl Texture2D {
         }
     }
 
+    /// Generate and allocate a texture with `pixels`, like `load_from_memory` but the length of
+    /// the pixels is checked against the size of the image
+    pub fn load_from_pixels(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        config: TextureConfig,
+    ) -> Result<(), TextureError> {
+        check_pixels_len(config.format.image_size(width, height), pixels)?;
+        unsafe {
+            self.load_from_memory(width, height, pixels.as_ptr() as *const c_void, config);
+        }
+        Ok(())
+    }
+
     // Generate and allocate a texture with given file path
     pub fn load_from_file(&mut self, filepath: &str, config: TextureConfig) {
         if self.config.is_some() {
@@ -255,6 +374,73 @@ impl Texture2D {
         }
     }
 
+    // Send data on a already allocated texture from a pixel buffer, the copy is done by the
+    // gpu later. The pixels start at `offset` bytes of the buffer
+    pub fn send_from_buffer(
+        &self,
+        xoffset: u32,
+        yoffset: u32,
+        width: u32,
+        height: u32,
+        buffer: &PixelBuffer,
+        offset: usize,
+    ) -> Result<(), TextureError> {
+        let config = self.config.as_ref().ok_or(TextureError::NotCreated)?;
+        check_pixels(offset, config.format.image_size(width, height), buffer)?;
+
+        self.bind();
+        buffer.transfer(gl::PIXEL_UNPACK_BUFFER, || unsafe {
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                xoffset as i32,
+                yoffset as i32,
+                width as i32,
+                height as i32,
+                config.format as u32,
+                gl::UNSIGNED_BYTE,
+                offset as *const c_void,
+            );
+        });
+        Ok(())
+    }
+
+    // Copy the pixels of the texture to a pixel buffer starting at `offset` bytes, the copy is
+    // done by the gpu later, use `PixelBuffer::is_ready` to know when the pixels can be read
+    pub fn read_into_buffer(
+        &self,
+        buffer: &PixelBuffer,
+        offset: usize,
+    ) -> Result<(), TextureError> {
+        let config = self.config.as_ref().ok_or(TextureError::NotCreated)?;
+        let (width, height) = self.level_size();
+        check_pixels(offset, config.format.image_size(width, height), buffer)?;
+
+        self.bind();
+        buffer.transfer(gl::PIXEL_PACK_BUFFER, || unsafe {
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                config.format as u32,
+                gl::UNSIGNED_BYTE,
+                offset as *mut c_void,
+            );
+        });
+        Ok(())
+    }
+
+    // The size of the texture as gl knows it
+    fn level_size(&self) -> (u32, u32) {
+        let mut width = 0;
+        let mut height = 0;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+        }
+        (width as u32, height as u32)
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, self.id);
@@ -262,6 +448,31 @@ impl Texture2D {
     }
 }
 
+fn check_pixels(offset: usize, size: usize, buffer: &PixelBuffer) -> Result<(), BufferError> {
+    if buffer.raw().is_mapped() {
+        return Err(BufferError::AlreadyMapped);
+    }
+    match offset.checked_add(size) {
+        Some(end) if end <= buffer.size() => Ok(()),
+        _ => Err(BufferError::OutOfBounds {
+            offset,
+            len: size,
+            capacity: buffer.size(),
+        }),
+    }
+}
+
+fn check_pixels_len(size: usize, pixels: &[u8]) -> Result<(), BufferError> {
+    if pixels.len() < size {
+        return Err(BufferError::OutOfBounds {
+            offset: 0,
+            len: size,
+            capacity: pixels.len(),
+        });
+    }
+    Ok(())
+}
+
 impl Drop for Texture2D {
     fn drop(&mut self) {
         unsafe {
@@ -269,3 +480,17 @@ impl Drop for Texture2D {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_size_doesnt_pad_the_last_row() {
+        assert_eq!(TextureFormat::Rgb.image_size(1, 1), 3);
+        assert_eq!(TextureFormat::Rgb.image_size(1, 2), 7);
+        assert_eq!(TextureFormat::Red.image_size(3, 3), 11);
+        assert_eq!(TextureFormat::Rgba.image_size(5, 2), 40);
+        assert_eq!(TextureFormat::Rgb.image_size(4, 0), 0);
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::buffers::{BufferError, GpuBuffer};
+
+/// How the captured varyings are written on the transform feedback buffers, the value is the
+/// gl enum given to `glTransformFeedbackVaryings`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackMode {
+    /// All the varyings are written one after another on the buffer 0
+    Interleaved = gl::INTERLEAVED_ATTRIBS as isize,
+    /// Each varying is written on its own buffer, the varying N on the buffer N
+    Separate = gl::SEPARATE_ATTRIBS as isize,
+}
+
+/// The primitives captured, they have to match the primitives drawn while the capture is active
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackPrimitive {
+    Points = gl::POINTS as isize,
+    Lines = gl::LINES as isize,
+    Triangles = gl::TRIANGLES as isize,
+}
+
+/// The state of a transform feedback object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackState {
+    Inactive,
+    Active,
+    Paused,
+}
+
+/// Errors returned by the operations on the transform feedback objects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedbackError {
+    /// The operation can't be done on the current state, like `pause` a inactive capture
+    InvalidState {
+        state: FeedbackState,
+        operation: &'static str,
+    },
+    /// The buffers can't change while the capture is active
+    Active,
+    /// The size in bytes of the range is zero or isn't a multiple of 4
+    InvalidSize(usize),
+    /// The range of the buffer isn't valid
+    Buffer(BufferError),
+}
+
+impl fmt::Display for FeedbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeedbackError::InvalidState { state, operation } => {
+                write!(f, "can't {} a {:?} transform feedback", operation, state)
+            }
+            FeedbackError::Active => {
+                write!(f, "the buffers can't change while the capture is active")
+            }
+            FeedbackError::InvalidSize(size) => {
+                write!(f, "size {} isn't a non zero multiple of 4 bytes", size)
+            }
+            FeedbackError::Buffer(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for FeedbackError {}
+
+impl From<BufferError> for FeedbackError {
+    fn from(err: BufferError) -> Self {
+        FeedbackError::Buffer(err)
+    }
+}
+
+/// A abstract representation of a transform feedback object, it captures the outputs of the
+/// last vertex stage on buffers. The outputs are declared on the shader with
+/// `Shader::set_feedback_varyings` before it's loaded. It keeps alive the buffers binded to it
+///  # Example
+/// ``` Rust
+/// let mut shader = Shader::new();
+/// shader.set_feedback_varyings(&["outPosition", "outVelocity"], FeedbackMode::Interleaved);
+/// shader.load_from_memory(UPDATE_VERTEX_SOURCE, EMPTY_FRAGMENT_SOURCE, None);
+///
+/// let particles = Rc::new(VertexBuffer::<Particle>::new(1000, None, BufferUsage::DynamicCopy)?);
+/// let mut feedback = TransformFeedback::new();
+/// feedback.bind_buffer(0, particles.clone())?;
+///
+/// shader.bind();
+/// unsafe { gl::Enable(gl::RASTERIZER_DISCARD) };
+/// feedback.begin(FeedbackPrimitive::Points)?;
+/// DrawCall::arrays(Topology::Points, 0..1000).submit(&previous_frame_vao)?;
+/// feedback.end()?;
+/// unsafe { gl::Disable(gl::RASTERIZER_DISCARD) };
+/// ```
+pub struct TransformFeedback {
+    id: u32,
+    buffers: Vec<Option<Rc<dyn GpuBuffer>>>,
+    state: Cell<FeedbackState>,
+}
+
+impl Default for TransformFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransformFeedback {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTransformFeedbacks(1, &mut id);
+        }
+        Self {
+            id,
+            buffers: Vec::new(),
+            state: Cell::new(FeedbackState::Inactive),
+        }
+    }
+
+    /// Bind the whole buffer to the binding point `index`, the varyings captured on that point
+    /// are written on it. It replaces the previous buffer of the point
+    pub fn bind_buffer<B: GpuBuffer + 'static>(
+        &mut self,
+        index: u32,
+        buffer: Rc<B>,
+    ) -> Result<(), FeedbackError> {
+        let size = buffer.raw().size();
+        self.bind_buffer_range(index, buffer, 0, size)
+    }
+
+    /// Bind `size` bytes of the buffer starting at `offset` bytes to the binding point `index`,
+    /// the offset and the size have to be a multiple of 4 and the size can't be zero
+    pub fn bind_buffer_range<B: GpuBuffer + 'static>(
+        &mut self,
+        index: u32,
+        buffer: Rc<B>,
+        offset: usize,
+        size: usize,
+    ) -> Result<(), FeedbackError> {
+        if self.state.get() != FeedbackState::Inactive {
+            return Err(FeedbackError::Active);
+        }
+        let raw = buffer.raw();
+        if offset.checked_add(size).is_none_or(|end| end > raw.size()) {
+            return Err(BufferError::OutOfBounds {
+                offset,
+                len: size,
+                capacity: raw.size(),
+            }
+            .into());
+        }
+        if !offset.is_multiple_of(4) {
+            return Err(BufferError::Misaligned { offset, align: 4 }.into());
+        }
+        if size == 0 || !size.is_multiple_of(4) {
+            return Err(FeedbackError::InvalidSize(size));
+        }
+
+        self.bind();
+        unsafe {
+            gl::BindBufferRange(
+                gl::TRANSFORM_FEEDBACK_BUFFER,
+                index,
+                raw.id(),
+                offset as isize,
+                size as isize,
+            );
+        }
+        self.unbind();
+
+        let index = index as usize;
+        if self.buffers.len() <= index {
+            self.buffers.resize(index + 1, None);
+        }
+        self.buffers[index] = Some(buffer);
+        Ok(())
+    }
+
+    /// Start to capture the primitives drawn after the call, the previous content of the
+    /// buffers is overwritten
+    pub fn begin(&self, primitive: FeedbackPrimitive) -> Result<(), FeedbackError> {
+        self.transition(FeedbackState::Inactive, FeedbackState::Active, "begin")?;
+        self.bind();
+        unsafe {
+            gl::BeginTransformFeedback(primitive as u32);
+        }
+        Ok(())
+    }
+
+    /// Stop the capture for a while, the draws done while it's paused aren't captured
+    pub fn pause(&self) -> Result<(), FeedbackError> {
+        self.transition(FeedbackState::Active, FeedbackState::Paused, "pause")?;
+        unsafe {
+            gl::PauseTransformFeedback();
+        }
+        Ok(())
+    }
+
+    /// Continue a paused capture, the primitives are written after the last ones captured
+    pub fn resume(&self) -> Result<(), FeedbackError> {
+        self.transition(FeedbackState::Paused, FeedbackState::Active, "resume")?;
+        self.bind();
+        unsafe {
+            gl::ResumeTransformFeedback();
+        }
+        Ok(())
+    }
+
+    /// Finish the capture
+    pub fn end(&self) -> Result<(), FeedbackError> {
+        if self.state.get() == FeedbackState::Inactive {
+            return Err(FeedbackError::InvalidState {
+                state: FeedbackState::Inactive,
+                operation: "end",
+            });
+        }
+        self.state.set(FeedbackState::Inactive);
+        self.bind();
+        unsafe {
+            gl::EndTransformFeedback();
+        }
+        self.unbind();
+        Ok(())
+    }
+
+    fn transition(
+        &self,
+        from: FeedbackState,
+        to: FeedbackState,
+        operation: &'static str,
+    ) -> Result<(), FeedbackError> {
+        if self.state.get() != from {
+            return Err(FeedbackError::InvalidState {
+                state: self.state.get(),
+                operation,
+            });
+        }
+        self.state.set(to);
+        Ok(())
+    }
+
+    pub fn state(&self) -> FeedbackState {
+        self.state.get()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindTransformFeedback(gl::TRANSFORM_FEEDBACK, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindTransformFeedback(gl::TRANSFORM_FEEDBACK, 0);
+        }
+    }
+}
+
+impl Drop for TransformFeedback {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTransformFeedbacks(1, &self.id);
+        }
+    }
+}